@@ -0,0 +1,164 @@
+use crate::{
+    block::Block,
+    grid::{
+        self,
+        components,
+        GridError,
+    },
+};
+use std::collections::HashMap;
+
+/// Switches and the toggle blocks they flip. Cells are indexes into the level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Circuit {
+    /// Every cell of the circuit, including wires
+    pub cells: Vec<usize>,
+    /// The Switch and SwitchCeiling cells
+    pub switches: Vec<usize>,
+    /// The ToggleBlock cells
+    pub toggles: Vec<usize>,
+}
+
+/// The circuits of a level, and which of them have been flipped.
+///
+/// Switches, ceiling switches, toggle blocks and wires that touch form a network.
+/// Activating a switch flips every toggle block in its network.
+/// Networks with switches but no toggle blocks, or toggle blocks but no switches, share one circuit,
+/// so unwired switches flip every unwired toggle block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Circuits {
+    blocks: Vec<Block>,
+    circuits: Vec<Circuit>,
+    /// The circuit every switch and toggle block is in
+    circuit_of: HashMap<usize, usize>,
+    flipped: Vec<bool>,
+}
+
+impl Circuits {
+    /// Find the circuits of a level. Nothing starts flipped.
+    pub fn analyze(blocks: &[Block]) -> Result<Self, GridError> {
+        let is_switch = |i: &usize| matches!(blocks[*i], Block::Switch | Block::SwitchCeiling);
+        let is_toggle = |i: &usize| matches!(blocks[*i], Block::ToggleBlock { .. });
+
+        let mut circuits = Vec::new();
+        let mut shared = Circuit {
+            cells: Vec::new(),
+            switches: Vec::new(),
+            toggles: Vec::new(),
+        };
+        for cells in networks(blocks)? {
+            let circuit = Circuit {
+                switches: cells.iter().copied().filter(is_switch).collect(),
+                toggles: cells.iter().copied().filter(is_toggle).collect(),
+                cells,
+            };
+            if circuit.switches.is_empty() || circuit.toggles.is_empty() {
+                shared.cells.extend(circuit.cells);
+                shared.switches.extend(circuit.switches);
+                shared.toggles.extend(circuit.toggles);
+            } else {
+                circuits.push(circuit);
+            }
+        }
+        if !shared.switches.is_empty() || !shared.toggles.is_empty() {
+            shared.cells.sort_unstable();
+            shared.switches.sort_unstable();
+            shared.toggles.sort_unstable();
+            circuits.push(shared);
+        }
+
+        let circuit_of = circuits
+            .iter()
+            .enumerate()
+            .flat_map(|(n, circuit)| {
+                circuit
+                    .switches
+                    .iter()
+                    .chain(circuit.toggles.iter())
+                    .map(move |i| (*i, n))
+            })
+            .collect();
+
+        Ok(Self {
+            blocks: blocks.to_vec(),
+            flipped: vec![false; circuits.len()],
+            circuits,
+            circuit_of,
+        })
+    }
+
+    /// The circuits, with wired circuits in reading order and the shared circuit last
+    pub fn circuits(&self) -> &[Circuit] {
+        &self.circuits
+    }
+
+    /// Get the index of the circuit a switch or toggle block is in
+    pub fn circuit_of(&self, i: usize) -> Option<usize> {
+        self.circuit_of.get(&i).copied()
+    }
+
+    /// Get the toggle blocks a switch flips. Empty if the cell isn't a switch.
+    pub fn affected(&self, switch: usize) -> &[usize] {
+        match self.circuit_of(switch) {
+            Some(n) if self.circuits[n].switches.contains(&switch) => &self.circuits[n].toggles,
+            _ => &[],
+        }
+    }
+
+    /// Activate a switch, flipping its circuit. Returns false if the cell isn't a switch.
+    pub fn activate(&mut self, switch: usize) -> bool {
+        self.activate_n(switch, 1)
+    }
+
+    /// Activate a switch n times. Only whether n is odd matters.
+    pub fn activate_n(&mut self, switch: usize, n: usize) -> bool {
+        match self.circuit_of(switch) {
+            Some(circuit) if self.circuits[circuit].switches.contains(&switch) => {
+                self.flipped[circuit] ^= n % 2 == 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns true if a circuit is flipped
+    pub fn is_flipped(&self, circuit: usize) -> bool {
+        self.flipped.get(circuit).copied().unwrap_or(false)
+    }
+
+    /// Unflip every circuit
+    pub fn reset(&mut self) {
+        self.flipped.iter_mut().for_each(|flipped| *flipped = false);
+    }
+
+    /// Get the level with the toggle blocks of flipped circuits flipped
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut ret = self.blocks.clone();
+        for (circuit, _) in self
+            .circuits
+            .iter()
+            .zip(self.flipped.iter())
+            .filter(|(_, flipped)| **flipped)
+        {
+            for i in circuit.toggles.iter().copied() {
+                if let Block::ToggleBlock { solid } = &mut ret[i] {
+                    *solid = !*solid;
+                }
+            }
+        }
+
+        ret
+    }
+}
+
+/// Get the groups of switches, ceiling switches, toggle blocks and wires that touch, in reading order
+fn networks(blocks: &[Block]) -> Result<Vec<Vec<usize>>, GridError> {
+    grid::check_length(blocks)?;
+
+    Ok(components(blocks, |block| {
+        matches!(
+            block,
+            Block::Wire | Block::Switch | Block::SwitchCeiling | Block::ToggleBlock { .. }
+        )
+    }))
+}
@@ -2,6 +2,10 @@
 pub mod as3;
 ///Utilities for working with the lbl file format
 pub mod lbl;
+/// Recording and verifying runs of levels
+pub mod replay;
+/// Recovering levels from screenshots
+pub mod screenshot;
 
 use crate::block::Block;
 
@@ -37,7 +37,7 @@ pub fn decode(data: &str) -> Result<(LevelNum, Vec<Block>), DecodeError> {
                 .enumerate()
             {
                 height += 1;
-                let new_level_num = parse_lhs(&lhs, i)?;
+                let new_level_num = parse_lhs(lhs, i)?;
                 match level_num.as_ref() {
                     Some(v) => {
                         if *v != new_level_num {
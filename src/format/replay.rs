@@ -0,0 +1,288 @@
+use crate::{
+    block::Block,
+    sim::{
+        Input,
+        SimError,
+        Simulation,
+        State,
+        Status,
+        SIM_VERSION,
+    },
+};
+
+/// The first line of every replay file
+pub const HEADER: &str = "sks-replay 1";
+
+/// A recorded run of a level
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    /// The [`level_hash`] of the level the run was recorded on
+    pub level_hash: u64,
+    /// The SIM_VERSION the run was recorded with
+    pub sim_version: u32,
+    /// The input of every tick
+    pub inputs: Vec<Input>,
+    /// The tick the run claims to finish on
+    pub finish_tick: u32,
+    /// The exit the run claims to reach
+    pub finish_status: Status,
+}
+
+impl Replay {
+    /// Record a run by playing it. The inputs are cut off where the player reaches an exit.
+    pub fn record(blocks: &[Block], inputs: &[Input]) -> Result<Self, ReplayError> {
+        let mut sim = Simulation::new(blocks)?;
+        let state = sim.run(inputs.iter().copied());
+        if !state.status.is_finished() {
+            return Err(ReplayError::NotFinished);
+        }
+
+        Ok(Self {
+            level_hash: level_hash(blocks),
+            sim_version: SIM_VERSION,
+            inputs: inputs[..state.tick as usize].to_vec(),
+            finish_tick: state.tick,
+            finish_status: state.status,
+        })
+    }
+
+    /// Play a replay and check that it finishes the way it claims to.
+    ///
+    /// Replays recorded on a different level or a different version of the simulation are rejected without playing them.
+    pub fn verify(&self, blocks: &[Block]) -> Result<State, ReplayError> {
+        if self.sim_version != SIM_VERSION {
+            return Err(ReplayError::SimVersion {
+                expected: SIM_VERSION,
+                found: self.sim_version,
+            });
+        }
+        let hash = level_hash(blocks);
+        if self.level_hash != hash {
+            return Err(ReplayError::LevelHash {
+                expected: hash,
+                found: self.level_hash,
+            });
+        }
+
+        let mut sim = Simulation::new(blocks)?;
+        let state = sim.run(self.inputs.iter().copied()).clone();
+        if !state.status.is_finished() {
+            return Err(ReplayError::NotFinished);
+        }
+        if state.tick != self.finish_tick || state.status != self.finish_status {
+            return Err(ReplayError::WrongFinish {
+                tick: state.tick,
+                status: state.status,
+            });
+        }
+
+        Ok(state)
+    }
+}
+
+/// Hash a level with 64 bit FNV-1a over its lbl. Stable across versions of this library.
+pub fn level_hash(blocks: &[Block]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    let mut hash = OFFSET_BASIS;
+    for block in blocks {
+        for byte in block.as_lbl().bytes().chain(std::iter::once(b'\n')) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(PRIME);
+        }
+    }
+
+    hash
+}
+
+/// Encode a replay as text.
+///
+/// After the header and a line for every field, inputs are run length encoded as lines of "count buttons".
+/// Buttons are any of L, R, J, B and C for left, right, jump, burrow and recall, or - for none.
+pub fn encode(replay: &Replay) -> String {
+    let mut ret = format!(
+        "{}\nsim-version {}\nlevel-hash {:016x}\nfinish {} {}\n",
+        HEADER,
+        replay.sim_version,
+        replay.level_hash,
+        replay.finish_tick,
+        status_name(replay.finish_status),
+    );
+
+    let mut inputs = replay.inputs.iter().peekable();
+    while let Some(input) = inputs.next() {
+        let mut count = 1;
+        while inputs.peek() == Some(&input) {
+            inputs.next();
+            count += 1;
+        }
+        ret += &format!("{} {}\n", count, encode_input(input));
+    }
+
+    ret
+}
+
+/// Parse a replay made by [`encode`]
+pub fn decode(data: &str) -> Result<Replay, DecodeError> {
+    let mut lines = data
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    match lines.next() {
+        Some((_, line)) if line.trim() == HEADER => {}
+        _ => return Err(DecodeError::MissingHeader),
+    }
+
+    let mut field = |name: &str| -> Result<(usize, Vec<&str>), DecodeError> {
+        let (n, line) = lines
+            .next()
+            .ok_or_else(|| DecodeError::MissingField(name.to_string()))?;
+        let mut parts = line.split_whitespace();
+        if parts.next() != Some(name) {
+            return Err(DecodeError::MissingField(name.to_string()));
+        }
+        Ok((n, parts.collect()))
+    };
+    let invalid = |n: usize| DecodeError::InvalidLine(n + 1);
+
+    let (n, parts) = field("sim-version")?;
+    let sim_version = match parts.as_slice() {
+        [version] => version.parse().map_err(|_| invalid(n))?,
+        _ => return Err(invalid(n)),
+    };
+    let (n, parts) = field("level-hash")?;
+    let level_hash = match parts.as_slice() {
+        [hash] => u64::from_str_radix(hash, 16).map_err(|_| invalid(n))?,
+        _ => return Err(invalid(n)),
+    };
+    let (n, parts) = field("finish")?;
+    let (finish_tick, finish_status) = match parts.as_slice() {
+        [tick, status] => (
+            tick.parse().map_err(|_| invalid(n))?,
+            parse_status(status).ok_or_else(|| invalid(n))?,
+        ),
+        _ => return Err(invalid(n)),
+    };
+
+    let mut inputs = Vec::new();
+    for (n, line) in lines {
+        let mut parts = line.split_whitespace();
+        let (count, input) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(count), Some(buttons), None) => (
+                count.parse::<usize>().map_err(|_| invalid(n))?,
+                decode_input(buttons).ok_or_else(|| invalid(n))?,
+            ),
+            _ => return Err(invalid(n)),
+        };
+        // Runs never go on past their finish, so bigger counts can only be corrupt
+        if count > finish_tick as usize - inputs.len() {
+            return Err(invalid(n));
+        }
+        inputs.extend(std::iter::repeat_n(input, count));
+    }
+
+    Ok(Replay {
+        level_hash,
+        sim_version,
+        inputs,
+        finish_tick,
+        finish_status,
+    })
+}
+
+fn status_name(status: Status) -> &'static str {
+    match status {
+        Status::Playing => "playing",
+        Status::Exited => "exit",
+        Status::SecretExited => "secret-exit",
+    }
+}
+
+fn parse_status(name: &str) -> Option<Status> {
+    [Status::Playing, Status::Exited, Status::SecretExited]
+        .iter()
+        .copied()
+        .find(|status| status_name(*status) == name)
+}
+
+fn encode_input(input: &Input) -> String {
+    let buttons = [
+        (input.left, 'L'),
+        (input.right, 'R'),
+        (input.jump, 'J'),
+        (input.burrow, 'B'),
+        (input.recall, 'C'),
+    ];
+    let ret: String = buttons
+        .iter()
+        .filter(|(held, _)| *held)
+        .map(|(_, c)| *c)
+        .collect();
+    if ret.is_empty() {
+        "-".to_string()
+    } else {
+        ret
+    }
+}
+
+fn decode_input(buttons: &str) -> Option<Input> {
+    let mut input = Input::none();
+    if buttons == "-" {
+        return Some(input);
+    }
+
+    for c in buttons.chars() {
+        let button = match c {
+            'L' => &mut input.left,
+            'R' => &mut input.right,
+            'J' => &mut input.jump,
+            'B' => &mut input.burrow,
+            'C' => &mut input.recall,
+            _ => return None,
+        };
+        *button = true;
+    }
+
+    Some(input)
+}
+
+/// Errors that can occur while parsing a replay
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The first line isn't HEADER
+    MissingHeader,
+    /// A field is missing or out of order
+    MissingField(String),
+    /// A line could not be parsed. Lines are numbered from 1.
+    InvalidLine(usize),
+}
+
+/// Errors that can occur while recording or verifying a replay
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    Sim(SimError),
+    /// The replay was recorded with a different version of the simulation
+    SimVersion {
+        expected: u32,
+        found: u32,
+    },
+    /// The replay was recorded on a different level
+    LevelHash {
+        expected: u64,
+        found: u64,
+    },
+    /// The inputs end before the player reaches an exit
+    NotFinished,
+    /// The run finishes differently than the replay claims
+    WrongFinish {
+        tick: u32,
+        status: Status,
+    },
+}
+
+impl From<SimError> for ReplayError {
+    fn from(e: SimError) -> Self {
+        Self::Sim(e)
+    }
+}
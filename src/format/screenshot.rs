@@ -0,0 +1,329 @@
+use crate::{
+    block::{
+        BackgroundType,
+        Block,
+        Direction,
+    },
+    render::{
+        center_offset,
+        ImageRenderer,
+        ImageRequest,
+        RenderError,
+        RenderOptions,
+        ScaleFilter,
+    },
+};
+use image::GenericImageView;
+
+/// The largest cell size cells are compared at. Bigger screenshots are scaled down first.
+const MAX_CELL_SIZE: u32 = 16;
+
+/// The most a channel may differ from the letterbox color and still count as letterbox
+const LETTERBOX_TOLERANCE: u8 = 8;
+
+/// Where the level is in a screenshot, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GridRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A level recovered from a screenshot
+#[derive(Debug, Clone, PartialEq)]
+pub struct Screenshot {
+    pub blocks: Vec<Block>,
+    /// How sure the match of every cell is, from 0 to 1, in the same order as blocks
+    pub confidence: Vec<f32>,
+    /// Where the level was found
+    pub grid: GridRect,
+    /// The background the level was drawn on. It is not placed in blocks, since it has no cell of its own.
+    pub background: BackgroundType,
+}
+
+/// Recover a level from a screenshot using the embedded textures. See [`decode_with`].
+pub fn decode(img: &image::DynamicImage) -> Result<Screenshot, DecodeError> {
+    decode_with(&ImageRenderer::new(), img)
+}
+
+/// Recover a level from a screenshot, matching cells against the textures of a renderer.
+///
+/// Letterboxing around the level is detected and removed. Every cell is then compared against every block with a texture, drawn on every background with a texture.
+///
+/// Some information can't be recovered:
+/// Blocks without a texture look like empty cells and come back as Block::Empty.
+/// Notes come back without their text.
+/// The background is returned separately, since a screenshot doesn't show which cell its block was in.
+pub fn decode_with(
+    renderer: &ImageRenderer,
+    img: &image::DynamicImage,
+) -> Result<Screenshot, DecodeError> {
+    let grid = detect_grid(img);
+    if grid.width < crate::LEVEL_WIDTH as u32 || grid.height < crate::LEVEL_HEIGHT as u32 {
+        return Err(DecodeError::TooSmall {
+            width: grid.width,
+            height: grid.height,
+        });
+    }
+
+    // Compare at the screenshot's own cell size where possible, so renders round trip exactly
+    let cell_size = (grid.width / crate::LEVEL_WIDTH as u32)
+        .min(grid.height / crate::LEVEL_HEIGHT as u32)
+        .min(MAX_CELL_SIZE);
+    let options = RenderOptions::new().cell_size(cell_size as usize);
+    let layout = options.layout();
+    let content = img.crop_imm(grid.x, grid.y, grid.width, grid.height);
+    let content = if content.dimensions() == (layout.width, layout.height) {
+        content.into_rgba8()
+    } else {
+        content
+            .resize_exact(
+                layout.width,
+                layout.height,
+                image::imageops::FilterType::Triangle,
+            )
+            .into_rgba8()
+    };
+
+    let candidates: Vec<Block> = candidate_blocks()
+        .into_iter()
+        .filter(|block| renderer.textures().get(block).is_some())
+        .collect();
+
+    let mut best: Option<BackgroundMatch> = None;
+    for background in candidate_backgrounds() {
+        if renderer.textures().get(&background).is_none() {
+            continue;
+        }
+
+        let mut level = vec![Block::Empty; crate::LEVEL_SIZE];
+        level[0] = background.clone();
+        let bg_img = renderer.render(&level, &options)?.into_rgba8();
+
+        let mut total = 0;
+        let mut cells = Vec::with_capacity(crate::LEVEL_SIZE);
+        for i in 0..crate::LEVEL_SIZE {
+            let (x, y) = (
+                (i % crate::LEVEL_WIDTH) as u32,
+                (i / crate::LEVEL_WIDTH) as u32,
+            );
+            let (cell_x, cell_y, w, h) = layout.cell_rect(x, y);
+            let cell = content.view(cell_x, cell_y, w, h).to_image();
+            let bg_cell = bg_img.view(cell_x, cell_y, w, h).to_image();
+
+            let mut errors = vec![(Block::Empty, cell_error(&cell, &bg_cell))];
+            for block in candidates.iter() {
+                let request = ImageRequest {
+                    w,
+                    h,
+                    block: block.clone(),
+                    filter: ScaleFilter::Smooth,
+                };
+                if let Some(block_img) = renderer.get_rendered(request) {
+                    let mut template = bg_cell.clone();
+                    let (dx, dy) = center_offset(&block_img, w, h);
+                    image::imageops::overlay(&mut template, &*block_img, dx, dy);
+                    errors.push((block.clone(), cell_error(&cell, &template)));
+                }
+            }
+            errors.sort_by_key(|(_, error)| *error);
+
+            let (block, error) = errors[0].clone();
+            let next_error = errors.get(1).map(|(_, error)| *error);
+            total += error;
+            cells.push((block, confidence(error, next_error, w * h)));
+        }
+
+        if best.as_ref().is_none_or(|best| total < best.total) {
+            best = Some(BackgroundMatch {
+                total,
+                background,
+                cells,
+            });
+        }
+    }
+
+    let BackgroundMatch {
+        background, cells, ..
+    } = best.ok_or(RenderError::MissingBackgroundTexture)?;
+    let (blocks, confidence): (Vec<_>, Vec<_>) = cells.into_iter().unzip();
+    let background = match background {
+        Block::Background { background_type } => background_type,
+        _ => BackgroundType::Cobble,
+    };
+
+    Ok(Screenshot {
+        blocks,
+        confidence,
+        grid,
+        background,
+    })
+}
+
+/// The cells matched on top of a background
+struct BackgroundMatch {
+    /// The sum of the errors of every cell
+    total: u64,
+    background: Block,
+    /// The best block for every cell, with its confidence
+    cells: Vec<(Block, f32)>,
+}
+
+/// Find the level in a screenshot by removing borders of the corner color. Returns the whole image if there are none.
+pub fn detect_grid(img: &image::DynamicImage) -> GridRect {
+    let img = img.to_rgba8();
+    let (width, height) = img.dimensions();
+    let whole = GridRect {
+        x: 0,
+        y: 0,
+        width,
+        height,
+    };
+    if width == 0 || height == 0 {
+        return whole;
+    }
+
+    let letterbox = *img.get_pixel(0, 0);
+    let is_letterbox = |x: u32, y: u32| {
+        img.get_pixel(x, y)
+            .0
+            .iter()
+            .zip(letterbox.0.iter())
+            .all(|(a, b)| (i16::from(*a) - i16::from(*b)).abs() <= i16::from(LETTERBOX_TOLERANCE))
+    };
+    let corners = [(width - 1, 0), (0, height - 1), (width - 1, height - 1)];
+    if !corners.iter().all(|(x, y)| is_letterbox(*x, *y)) {
+        return whole;
+    }
+
+    let row_is_letterbox = |y: u32| (0..width).all(|x| is_letterbox(x, y));
+    let col_is_letterbox = |x: u32| (0..height).all(|y| is_letterbox(x, y));
+    let top = match (0..height).find(|y| !row_is_letterbox(*y)) {
+        Some(top) => top,
+        None => return whole,
+    };
+    let bottom = (0..height)
+        .rev()
+        .find(|y| !row_is_letterbox(*y))
+        .unwrap_or(top);
+    let left = (0..width).find(|x| !col_is_letterbox(*x)).unwrap_or(0);
+    let right = (0..width)
+        .rev()
+        .find(|x| !col_is_letterbox(*x))
+        .unwrap_or(left);
+
+    GridRect {
+        x: left,
+        y: top,
+        width: right - left + 1,
+        height: bottom - top + 1,
+    }
+}
+
+/// The sum of squared differences of the rgb channels of two images of the same size
+fn cell_error(a: &image::RgbaImage, b: &image::RgbaImage) -> u64 {
+    a.pixels()
+        .zip(b.pixels())
+        .map(|(a, b)| {
+            a.0[..3]
+                .iter()
+                .zip(b.0[..3].iter())
+                .map(|(a, b)| {
+                    let d = i64::from(*a) - i64::from(*b);
+                    (d * d) as u64
+                })
+                .sum::<u64>()
+        })
+        .sum()
+}
+
+/// Combine how close the best match is with how far ahead of the runner up it is
+fn confidence(error: u64, next_error: Option<u64>, pixels: u32) -> f32 {
+    let rms = (error as f64 / (f64::from(pixels.max(1)) * 3.0)).sqrt();
+    let similarity = 1.0 - rms / 255.0;
+    // Templates that match equally well can't be told apart, even if both match exactly
+    let separation = match next_error {
+        None => 1.0,
+        Some(next_error) if next_error == error => 0.0,
+        Some(next_error) => 1.0 - error as f64 / next_error as f64,
+    };
+
+    (similarity * separation) as f32
+}
+
+/// Every block that isn't a background or empty, with default data
+fn candidate_blocks() -> Vec<Block> {
+    vec![
+        Block::Block,
+        Block::Dark,
+        Block::Exit,
+        Block::Key,
+        Block::Lock,
+        Block::Note {
+            text: String::new(),
+        },
+        Block::Scaffold,
+        Block::SecretExit,
+        Block::Switch,
+        Block::SwitchCeiling,
+        Block::OneWayWall {
+            direction: Direction::Up,
+        },
+        Block::OneWayWall {
+            direction: Direction::Down,
+        },
+        Block::OneWayWall {
+            direction: Direction::Left,
+        },
+        Block::OneWayWall {
+            direction: Direction::Right,
+        },
+        Block::PipeIn,
+        Block::PipeOut,
+        Block::PipePhase,
+        Block::PipeSolid,
+        Block::Player,
+        Block::PowerUpBurrow,
+        Block::PowerUpRecall,
+        Block::ToggleBlock { solid: true },
+        Block::ToggleBlock { solid: false },
+        Block::Torch,
+        Block::Wire,
+    ]
+}
+
+/// Every background block
+fn candidate_backgrounds() -> Vec<Block> {
+    [
+        BackgroundType::Cobble,
+        BackgroundType::Waterfall,
+        BackgroundType::Skullfall,
+        BackgroundType::Concrete,
+        BackgroundType::Reserved1,
+        BackgroundType::Reserved2,
+        BackgroundType::Reserved3,
+    ]
+    .iter()
+    .map(|background_type| Block::Background {
+        background_type: background_type.clone(),
+    })
+    .collect()
+}
+
+/// Errors that can occur while recovering a level from a screenshot
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The level area is smaller than one pixel per cell
+    TooSmall {
+        width: u32,
+        height: u32,
+    },
+    Render(RenderError),
+}
+
+impl From<RenderError> for DecodeError {
+    fn from(e: RenderError) -> Self {
+        Self::Render(e)
+    }
+}
@@ -0,0 +1,521 @@
+use crate::{
+    block::{
+        BackgroundType,
+        Block,
+        Direction,
+    },
+    lint::{
+        lint,
+        LintOptions,
+    },
+    sim::difficulty::{
+        estimate,
+        Difficulty,
+        DifficultyOptions,
+        Features,
+    },
+    LEVEL_HEIGHT,
+    LEVEL_SIZE,
+    LEVEL_WIDTH,
+};
+use std::mem::discriminant;
+
+/// The highest floor of the tunnel. Leaves room for headroom and the ceiling above it.
+const MIN_FLOOR: usize = 6;
+/// The lowest floor of the tunnel. Leaves room for pipes and pockets below it.
+const MAX_FLOOR: usize = LEVEL_HEIGHT - 4;
+/// The most mechanics placed when more than the required ones are added. Levels with more take too long to solve.
+const MAX_MECHANICS: usize = 4;
+
+/// Something a generated level can make the player do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mechanic {
+    /// Pick up a key to open a lock
+    Key,
+    /// Walk over a switch to open a toggle block
+    Switch,
+    /// Pass through a one way wall
+    OneWayWall,
+    /// Take a pipe under a wall
+    Pipe,
+    /// Burrow down to the exit
+    Burrow,
+    /// Recall out of a pit after picking up a key in it
+    Recall,
+}
+
+impl Mechanic {
+    /// Every mechanic
+    pub const ALL: [Mechanic; 6] = [
+        Mechanic::Key,
+        Mechanic::Switch,
+        Mechanic::OneWayWall,
+        Mechanic::Pipe,
+        Mechanic::Burrow,
+        Mechanic::Recall,
+    ];
+
+    /// The blocks a mechanic places
+    pub fn blocks(&self) -> Vec<Block> {
+        let one_way = |direction| Block::OneWayWall { direction };
+        match self {
+            Mechanic::Key => vec![Block::Key, Block::Lock],
+            Mechanic::Switch => vec![Block::Switch, Block::ToggleBlock { solid: true }],
+            Mechanic::OneWayWall => vec![one_way(Direction::Right)],
+            Mechanic::Pipe => vec![Block::PipeIn, Block::PipeOut, Block::PipeSolid],
+            Mechanic::Burrow => vec![Block::PowerUpBurrow],
+            Mechanic::Recall => vec![
+                one_way(Direction::Right),
+                Block::PowerUpRecall,
+                one_way(Direction::Down),
+                Block::Key,
+                Block::Lock,
+            ],
+        }
+    }
+
+    /// The number of columns a mechanic takes up
+    fn width(&self) -> usize {
+        match self {
+            Mechanic::Key | Mechanic::Switch => 4,
+            Mechanic::OneWayWall => 3,
+            Mechanic::Pipe => 6,
+            Mechanic::Recall => 7,
+            Mechanic::Burrow => 5,
+        }
+    }
+
+    /// Returns true if a solution with some features must have used a mechanic.
+    /// Pipes and one way walls are the only way past their walls, so they are always used.
+    fn is_used(&self, features: &Features) -> bool {
+        match self {
+            Mechanic::Key => features.locks > 0,
+            Mechanic::Switch => features.switches > 0,
+            Mechanic::OneWayWall | Mechanic::Pipe => true,
+            Mechanic::Burrow => features.burrows > 0,
+            Mechanic::Recall => features.recalls > 0,
+        }
+    }
+}
+
+/// Options for generating levels
+#[derive(Debug, Clone)]
+pub struct GeneratorOptions {
+    /// The same seed and options always generate the same level
+    pub seed: u64,
+    /// Blocks that may be placed, besides Block, Empty, Player and Exit. Only the kind of block matters, not its data.
+    pub allowed: Vec<Block>,
+    /// Mechanics every generated level uses
+    pub required: Vec<Mechanic>,
+    /// The background of generated levels. None leaves out the Background block.
+    pub background: Option<BackgroundType>,
+    /// The difficulty score to aim for, if any
+    pub target_difficulty: Option<f32>,
+    /// How far from target_difficulty a score may be
+    pub difficulty_tolerance: f32,
+    /// The number of levels to try before giving up
+    pub max_attempts: usize,
+    /// The options used to solve and score levels
+    pub difficulty: DifficultyOptions,
+}
+
+impl GeneratorOptions {
+    /// Default GeneratorOptions. Every mechanic and torches are allowed.
+    pub fn new() -> Self {
+        let mut allowed: Vec<Block> = Mechanic::ALL.iter().flat_map(|m| m.blocks()).collect();
+        allowed.push(Block::Torch);
+
+        Self {
+            seed: 0,
+            allowed,
+            required: Vec::new(),
+            background: None,
+            target_difficulty: None,
+            difficulty_tolerance: 5.0,
+            max_attempts: 32,
+            difficulty: DifficultyOptions::new(),
+        }
+    }
+
+    /// The seed of the generator
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// The blocks that may be placed
+    pub fn allowed(mut self, allowed: Vec<Block>) -> Self {
+        self.allowed = allowed;
+        self
+    }
+
+    /// Require a mechanic to be used
+    pub fn require(mut self, mechanic: Mechanic) -> Self {
+        if !self.required.contains(&mechanic) {
+            self.required.push(mechanic);
+        }
+        self
+    }
+
+    /// The background of generated levels
+    pub fn background(mut self, background: BackgroundType) -> Self {
+        self.background = Some(background);
+        self
+    }
+
+    /// Aim for a difficulty score, give or take a tolerance
+    pub fn target_difficulty(mut self, target: f32, tolerance: f32) -> Self {
+        self.target_difficulty = Some(target);
+        self.difficulty_tolerance = tolerance;
+        self
+    }
+
+    /// The number of levels to try before giving up
+    pub fn max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// The options used to solve and score levels
+    pub fn difficulty(mut self, difficulty: DifficultyOptions) -> Self {
+        self.difficulty = difficulty;
+        self
+    }
+
+    /// Returns true if a block may be placed
+    pub fn is_allowed(&self, block: &Block) -> bool {
+        self.allowed
+            .iter()
+            .any(|allowed| discriminant(allowed) == discriminant(block))
+    }
+
+    /// Returns true if every block of a mechanic may be placed
+    pub fn is_mechanic_allowed(&self, mechanic: Mechanic) -> bool {
+        mechanic.blocks().iter().all(|block| self.is_allowed(block))
+    }
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A generated level
+#[derive(Debug, Clone, PartialEq)]
+pub struct Generated {
+    pub blocks: Vec<Block>,
+    /// The mechanics placed in the level, from left to right
+    pub mechanics: Vec<Mechanic>,
+    pub difficulty: Difficulty,
+    /// The number of levels tried, including this one
+    pub attempts: usize,
+}
+
+/// Generate a level.
+///
+/// Levels are a tunnel from the player on the left to the exit on the right, with a stepped floor
+/// and mechanics along the way that block the tunnel until they are used.
+/// Only levels without lint diagnostics that the solver can beat, using every required mechanic,
+/// within the target difficulty are returned.
+/// After every attempt that scores too low or too high, the next one places more or fewer mechanics.
+pub fn generate(options: &GeneratorOptions) -> Result<Generated, GenerateError> {
+    for mechanic in options.required.iter() {
+        if !options.is_mechanic_allowed(*mechanic) {
+            return Err(GenerateError::NotAllowed(*mechanic));
+        }
+    }
+    let fixed: usize = options.required.iter().map(|m| m.width()).sum();
+    let end = if options.required.contains(&Mechanic::Burrow) {
+        0
+    } else {
+        1
+    };
+    if 2 + fixed + end > LEVEL_WIDTH - 2 {
+        return Err(GenerateError::TooManyMechanics);
+    }
+
+    let optional: Vec<Mechanic> = Mechanic::ALL
+        .iter()
+        .copied()
+        .filter(|m| !options.required.contains(m) && options.is_mechanic_allowed(*m))
+        .collect();
+    let max_extras = optional
+        .len()
+        .min(MAX_MECHANICS.saturating_sub(options.required.len()));
+
+    let mut rng = Rng::new(options.seed);
+    let mut extras = rng.below(max_extras + 1);
+    for attempt in 1..=options.max_attempts {
+        let roughness = rng.below(3);
+        let mut mechanics = options.required.clone();
+        let mut candidates = optional.clone();
+        rng.shuffle(&mut candidates);
+        mechanics.extend(candidates.into_iter().take(extras));
+        rng.shuffle(&mut mechanics);
+
+        let blocks = match build(&mut rng, options, &mut mechanics, roughness) {
+            Some(blocks) => blocks,
+            None => continue,
+        };
+        let lint_options = LintOptions::new();
+        if !lint(&blocks, &lint_options).is_ok_and(|diagnostics| diagnostics.is_empty()) {
+            continue;
+        }
+        let difficulty = match estimate(&blocks, &options.difficulty) {
+            Ok(difficulty) => difficulty,
+            Err(_) => continue,
+        };
+        if !options
+            .required
+            .iter()
+            .all(|m| m.is_used(&difficulty.features))
+        {
+            continue;
+        }
+
+        match options.target_difficulty {
+            Some(target) if difficulty.score < target - options.difficulty_tolerance => {
+                extras = (extras + 1).min(max_extras);
+            }
+            Some(target) if difficulty.score > target + options.difficulty_tolerance => {
+                extras = extras.saturating_sub(1);
+            }
+            _ => {
+                return Ok(Generated {
+                    blocks,
+                    mechanics,
+                    difficulty,
+                    attempts: attempt,
+                })
+            }
+        }
+    }
+
+    Err(GenerateError::GaveUp)
+}
+
+/// Lay out a level. Mechanics that don't fit are dropped, unless they are required.
+fn build(
+    rng: &mut Rng,
+    options: &GeneratorOptions,
+    mechanics: &mut Vec<Mechanic>,
+    roughness: usize,
+) -> Option<Vec<Block>> {
+    // Burrowing down to the exit has to come last
+    if let Some(n) = mechanics.iter().position(|m| *m == Mechanic::Burrow) {
+        let burrow = mechanics.remove(n);
+        mechanics.push(burrow);
+    }
+    let inner = LEVEL_WIDTH - 2;
+    let end_width = |mechanics: &[Mechanic]| match mechanics.last() {
+        Some(Mechanic::Burrow) => 0,
+        _ => 1,
+    };
+    let used = |mechanics: &[Mechanic]| {
+        2 + mechanics.iter().map(|m| m.width()).sum::<usize>() + end_width(mechanics)
+    };
+    while used(mechanics) > inner {
+        let n = mechanics
+            .iter()
+            .rposition(|m| !options.required.contains(m))?;
+        mechanics.remove(n);
+    }
+
+    // Hand out the leftover columns as stepped floor between the pieces
+    let mut terrain = vec![0; mechanics.len() + 1];
+    for _ in 0..inner - used(mechanics) {
+        let n = rng.below(terrain.len());
+        terrain[n] += 1;
+    }
+
+    let mut level = Tunnel::new();
+    let mut floor = MIN_FLOOR + 3 + rng.below(MAX_FLOOR - MIN_FLOOR - 4);
+    let mut x = 1;
+    level.column(x, floor);
+    level.set(x, floor - 1, Block::Player);
+    level.column(x + 1, floor);
+    x += 2;
+
+    let torch = options.is_allowed(&Block::Torch);
+    for (n, columns) in terrain.iter().copied().enumerate() {
+        for _ in 0..columns {
+            if rng.below(4) < roughness {
+                floor = if rng.below(2) == 0 {
+                    floor - 1
+                } else {
+                    floor + 1
+                };
+                floor = floor.clamp(MIN_FLOOR, MAX_FLOOR);
+            }
+            level.column(x, floor);
+            if torch && rng.below(6) == 0 {
+                level.set(x, floor - 3, Block::Torch);
+            }
+            x += 1;
+        }
+        if let Some(mechanic) = mechanics.get(n) {
+            level.piece(x, floor, *mechanic);
+            x += mechanic.width();
+        }
+    }
+    if end_width(mechanics) == 1 {
+        level.column(x, floor);
+        level.set(x, floor - 1, Block::Exit);
+    }
+
+    if let Some(background_type) = &options.background {
+        level.set(
+            0,
+            0,
+            Block::Background {
+                background_type: background_type.clone(),
+            },
+        );
+    }
+
+    Some(level.blocks)
+}
+
+/// A level being carved out of solid blocks
+struct Tunnel {
+    blocks: Vec<Block>,
+}
+
+impl Tunnel {
+    fn new() -> Self {
+        Self {
+            blocks: vec![Block::Block; LEVEL_SIZE],
+        }
+    }
+
+    fn set(&mut self, x: usize, y: usize, block: Block) {
+        self.blocks[y * LEVEL_WIDTH + x] = block;
+    }
+
+    /// Carve the three cells above a floor
+    fn column(&mut self, x: usize, floor: usize) {
+        for y in floor - 3..floor {
+            self.set(x, y, Block::Empty);
+        }
+    }
+
+    /// Fill a column and put a door in the player's row
+    fn door(&mut self, x: usize, floor: usize, door: Block) {
+        self.wall(x, floor);
+        self.set(x, floor - 1, door);
+    }
+
+    /// Place a mechanic, starting at a column
+    fn piece(&mut self, x: usize, floor: usize, mechanic: Mechanic) {
+        for dx in 0..mechanic.width() {
+            self.column(x + dx, floor);
+        }
+        let row = floor - 1;
+        match mechanic {
+            Mechanic::Key => {
+                self.set(x + 1, row, Block::Key);
+                self.door(x + 3, floor, Block::Lock);
+            }
+            Mechanic::Switch => {
+                self.set(x + 1, row, Block::Switch);
+                self.door(x + 3, floor, Block::ToggleBlock { solid: true });
+            }
+            Mechanic::OneWayWall => {
+                self.door(
+                    x + 1,
+                    floor,
+                    Block::OneWayWall {
+                        direction: Direction::Right,
+                    },
+                );
+            }
+            Mechanic::Pipe => {
+                // Down through the floor, under the wall, and up to the exit on the other side
+                self.set(x + 1, floor, Block::PipeIn);
+                for dx in 1..5 {
+                    self.set(x + dx, floor + 1, Block::PipeSolid);
+                }
+                self.wall(x + 3, floor);
+                self.set(x + 4, floor, Block::PipeSolid);
+                self.set(x + 4, row, Block::PipeOut);
+            }
+            Mechanic::Recall => {
+                // The gate and the low ceiling keep the places a recall point can be marked few
+                for dx in 0..6 {
+                    self.wall(x + dx, floor);
+                    self.set(x + dx, row, Block::Empty);
+                }
+                self.door(
+                    x,
+                    floor,
+                    Block::OneWayWall {
+                        direction: Direction::Right,
+                    },
+                );
+                self.set(x + 2, row, Block::PowerUpRecall);
+                self.set(
+                    x + 4,
+                    floor,
+                    Block::OneWayWall {
+                        direction: Direction::Down,
+                    },
+                );
+                self.set(x + 4, floor + 1, Block::Key);
+                self.door(x + 6, floor, Block::Lock);
+            }
+            Mechanic::Burrow => {
+                self.set(x + 1, row, Block::PowerUpBurrow);
+                self.set(x + 3, floor + 1, Block::Empty);
+                self.set(x + 4, floor + 1, Block::Exit);
+            }
+        }
+    }
+
+    /// Fill a carved column back in
+    fn wall(&mut self, x: usize, floor: usize) {
+        for y in floor - 3..floor {
+            self.set(x, y, Block::Block);
+        }
+    }
+}
+
+/// A small seeded random number generator (SplitMix64), so levels don't change with dependency versions
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Get a number below n. n must not be 0.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i + 1);
+            items.swap(i, j);
+        }
+    }
+}
+
+/// Errors that may occur while generating levels
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GenerateError {
+    /// A required mechanic needs a block that isn't allowed
+    NotAllowed(Mechanic),
+    /// The required mechanics don't fit in one level
+    TooManyMechanics,
+    /// No attempt made a level that met the options
+    GaveUp,
+}
@@ -0,0 +1,71 @@
+use crate::block::Block;
+
+/// Get the 4-connected groups of cells for which include returns true, in reading order
+pub(crate) fn components<F>(blocks: &[Block], include: F) -> Vec<Vec<usize>>
+where
+    F: Fn(&Block) -> bool,
+{
+    let mut seen = vec![false; blocks.len()];
+    let mut ret = Vec::new();
+    for start in 0..blocks.len() {
+        if seen[start] || !include(&blocks[start]) {
+            continue;
+        }
+
+        seen[start] = true;
+        let mut cells = Vec::new();
+        let mut stack = vec![start];
+        while let Some(i) = stack.pop() {
+            cells.push(i);
+            for n in neighbours(i) {
+                if !seen[n] && include(&blocks[n]) {
+                    seen[n] = true;
+                    stack.push(n);
+                }
+            }
+        }
+
+        cells.sort_unstable();
+        ret.push(cells);
+    }
+
+    ret
+}
+
+/// Get the indexes of the cells above, below, left, and right of a cell
+pub(crate) fn neighbours(i: usize) -> impl Iterator<Item = usize> {
+    let (x, y) = (i % crate::LEVEL_WIDTH, i / crate::LEVEL_WIDTH);
+    let up = if y > 0 {
+        Some(i - crate::LEVEL_WIDTH)
+    } else {
+        None
+    };
+    let down = if y + 1 < crate::LEVEL_HEIGHT {
+        Some(i + crate::LEVEL_WIDTH)
+    } else {
+        None
+    };
+    let left = if x > 0 { Some(i - 1) } else { None };
+    let right = if x + 1 < crate::LEVEL_WIDTH {
+        Some(i + 1)
+    } else {
+        None
+    };
+
+    up.into_iter().chain(down).chain(left).chain(right)
+}
+
+/// Check that a level has a block for every cell
+pub(crate) fn check_length(blocks: &[Block]) -> Result<(), GridError> {
+    if blocks.len() != crate::LEVEL_SIZE {
+        return Err(GridError::InvalidLength(blocks.len()));
+    }
+
+    Ok(())
+}
+
+/// Errors that may occur while analyzing the cells of a level
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GridError {
+    InvalidLength(usize),
+}
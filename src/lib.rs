@@ -1,9 +1,21 @@
 /// Utilities related to how this lib represents blocks
 pub mod block;
+/// Modeling the switches, wires and toggle blocks of levels
+pub mod circuit;
 /// Utilities for working with file formats
 pub mod format;
+/// Generating new levels
+pub mod generate;
+/// How the cells of a level connect
+pub mod grid;
+/// Checking levels for mistakes
+pub mod lint;
+/// Tracing the pipe networks of levels
+pub mod pipe;
 /// Utilities for rendering blocks
 pub mod render;
+/// Simulating levels being played
+pub mod sim;
 
 /// The width of a level, in blocks
 pub const LEVEL_WIDTH: usize = 32;
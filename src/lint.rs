@@ -0,0 +1,451 @@
+use crate::{
+    block::Block,
+    circuit::Circuits,
+    grid::GridError,
+    pipe::{
+        PipeProblemKind,
+        Pipes,
+    },
+};
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+
+/// How serious a problem is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The level works, but probably not as intended
+    Warning,
+    /// The level is broken
+    Error,
+}
+
+impl Severity {
+    /// Get the lowercase name of this severity
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+}
+
+/// A check that levels are run through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// There must be exactly one Player
+    SinglePlayer,
+    /// There must be at least one Exit
+    MissingExit,
+    /// There must be a Key for every Lock
+    LockWithoutKey,
+    /// There should be at most one Background block
+    MultipleBackgrounds,
+    /// Every PipeIn should lead to a PipeOut, and every PipeOut should have a PipeIn
+    UnpairedPipe,
+    /// Pipes should not end anywhere but at a PipeIn or PipeOut
+    DeadEndPipe,
+    /// ToggleBlocks need a Switch or SwitchCeiling in their circuit to flip them
+    ToggleWithoutSwitch,
+    /// Notes should have text
+    EmptyNote,
+    /// Note text shouldn't end lines with whitespace
+    NoteWhitespace,
+}
+
+impl Rule {
+    /// Every rule
+    pub const ALL: [Rule; 9] = [
+        Rule::SinglePlayer,
+        Rule::MissingExit,
+        Rule::LockWithoutKey,
+        Rule::MultipleBackgrounds,
+        Rule::UnpairedPipe,
+        Rule::DeadEndPipe,
+        Rule::ToggleWithoutSwitch,
+        Rule::EmptyNote,
+        Rule::NoteWhitespace,
+    ];
+
+    /// Get the kebab-case name of this rule
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SinglePlayer => "single-player",
+            Self::MissingExit => "missing-exit",
+            Self::LockWithoutKey => "lock-without-key",
+            Self::MultipleBackgrounds => "multiple-backgrounds",
+            Self::UnpairedPipe => "unpaired-pipe",
+            Self::DeadEndPipe => "dead-end-pipe",
+            Self::ToggleWithoutSwitch => "toggle-without-switch",
+            Self::EmptyNote => "empty-note",
+            Self::NoteWhitespace => "note-whitespace",
+        }
+    }
+
+    /// Get a rule from its name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|rule| rule.name() == name)
+    }
+
+    /// The severity of problems found by this rule, unless configured otherwise
+    pub fn default_severity(&self) -> Severity {
+        match self {
+            Self::SinglePlayer | Self::MissingExit | Self::LockWithoutKey => Severity::Error,
+            Self::MultipleBackgrounds
+            | Self::UnpairedPipe
+            | Self::DeadEndPipe
+            | Self::ToggleWithoutSwitch
+            | Self::EmptyNote
+            | Self::NoteWhitespace => Severity::Warning,
+        }
+    }
+}
+
+/// A problem found in a level
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub rule: Rule,
+    pub severity: Severity,
+    pub message: String,
+    /// The (x, y) cells the problem is at. Empty for problems with the whole level.
+    pub cells: Vec<(usize, usize)>,
+    /// A change to the level that solves the problem, if there is a mechanical one
+    pub fix: Option<Fix>,
+}
+
+/// Changes to a level that solve a problem. See [`apply_fixes`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fix {
+    pub description: String,
+    pub edits: Vec<Edit>,
+}
+
+/// Replace the block in a cell
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edit {
+    /// The (x, y) of the cell
+    pub cell: (usize, usize),
+    pub block: Block,
+}
+
+impl std::fmt::Display for Diagnostic {
+    /// Formats as "severity[rule]: message (at x,y ...)"
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}[{}]: {}",
+            self.severity.name(),
+            self.rule.name(),
+            self.message
+        )?;
+        if !self.cells.is_empty() {
+            let cells: Vec<String> = self
+                .cells
+                .iter()
+                .map(|(x, y)| format!("{},{}", x, y))
+                .collect();
+            write!(f, " (at {})", cells.join(" "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Which rules to run, and how serious their problems are
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintOptions {
+    /// Rules that are not run
+    pub disabled: HashSet<Rule>,
+    /// Severities used instead of the defaults of rules
+    pub severities: HashMap<Rule, Severity>,
+}
+
+impl LintOptions {
+    /// Default LintOptions. Every rule is enabled.
+    pub fn new() -> Self {
+        Self {
+            disabled: HashSet::new(),
+            severities: HashMap::new(),
+        }
+    }
+
+    /// Run a rule
+    pub fn enable(mut self, rule: Rule) -> Self {
+        self.disabled.remove(&rule);
+        self
+    }
+
+    /// Don't run a rule
+    pub fn disable(mut self, rule: Rule) -> Self {
+        self.disabled.insert(rule);
+        self
+    }
+
+    /// Change the severity of a rule
+    pub fn severity(mut self, rule: Rule, severity: Severity) -> Self {
+        self.severities.insert(rule, severity);
+        self
+    }
+
+    /// Returns true if a rule is run
+    pub fn is_enabled(&self, rule: Rule) -> bool {
+        !self.disabled.contains(&rule)
+    }
+
+    /// Get the severity of a rule
+    pub fn severity_of(&self, rule: Rule) -> Severity {
+        self.severities
+            .get(&rule)
+            .copied()
+            .unwrap_or_else(|| rule.default_severity())
+    }
+}
+
+impl Default for LintOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Check a level for problems. Diagnostics are in the order of Rule::ALL.
+pub fn lint(blocks: &[Block], options: &LintOptions) -> Result<Vec<Diagnostic>, LintError> {
+    if blocks.len() != crate::LEVEL_SIZE {
+        return Err(LintError::InvalidLength(blocks.len()));
+    }
+
+    let mut diagnostics = Vec::new();
+    for rule in Rule::ALL.iter().copied() {
+        if !options.is_enabled(rule) {
+            continue;
+        }
+
+        let severity = options.severity_of(rule);
+        diagnostics.extend(check(rule, blocks)?.into_iter().map(|problem| Diagnostic {
+            rule,
+            severity,
+            message: problem.message,
+            cells: problem.cells.into_iter().map(cell_position).collect(),
+            fix: problem.fix,
+        }));
+    }
+
+    Ok(diagnostics)
+}
+
+/// A problem found by a rule, before it is turned into a Diagnostic
+struct Problem {
+    message: String,
+    cells: Vec<usize>,
+    fix: Option<Fix>,
+}
+
+impl Problem {
+    fn new(message: String, cells: Vec<usize>) -> Self {
+        Self {
+            message,
+            cells,
+            fix: None,
+        }
+    }
+
+    /// Attach a fix that replaces some cells
+    fn with_fix(mut self, description: &str, edits: Vec<(usize, Block)>) -> Self {
+        self.fix = Some(Fix {
+            description: description.to_string(),
+            edits: edits
+                .into_iter()
+                .map(|(i, block)| Edit {
+                    cell: cell_position(i),
+                    block,
+                })
+                .collect(),
+        });
+        self
+    }
+}
+
+/// Run a rule, returning every problem it finds
+fn check(rule: Rule, blocks: &[Block]) -> Result<Vec<Problem>, LintError> {
+    let cells_where = |f: &dyn Fn(&Block) -> bool| -> Vec<usize> {
+        blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, block)| f(block))
+            .map(|(i, _)| i)
+            .collect()
+    };
+    let remove = |cells: &[usize]| -> Vec<(usize, Block)> {
+        cells.iter().map(|i| (*i, Block::Empty)).collect()
+    };
+    let mut problems = Vec::new();
+
+    match rule {
+        Rule::SinglePlayer => {
+            let players = cells_where(&|block| *block == Block::Player);
+            if players.is_empty() {
+                problems.push(Problem::new("the level has no player".to_string(), players));
+            } else if players.len() > 1 {
+                let extra = &players[1..];
+                problems.push(
+                    Problem::new(
+                        format!("the level has {} players", players.len()),
+                        extra.to_vec(),
+                    )
+                    .with_fix("remove every player but the first", remove(extra)),
+                );
+            }
+        }
+        Rule::MissingExit => {
+            if cells_where(&|block| *block == Block::Exit).is_empty() {
+                problems.push(Problem::new(
+                    "the level has no exit".to_string(),
+                    Vec::new(),
+                ));
+            }
+        }
+        Rule::LockWithoutKey => {
+            let keys = cells_where(&|block| *block == Block::Key);
+            let locks = cells_where(&|block| *block == Block::Lock);
+            if locks.len() > keys.len() {
+                problems.push(Problem::new(
+                    format!("{} locks but only {} keys", locks.len(), keys.len()),
+                    locks,
+                ));
+            }
+        }
+        Rule::MultipleBackgrounds => {
+            let backgrounds = cells_where(&Block::is_background);
+            if backgrounds.len() > 1 {
+                // The renderer uses the last background
+                let extra = &backgrounds[..backgrounds.len() - 1];
+                problems.push(
+                    Problem::new(
+                        format!(
+                            "{} background blocks; only the last is used",
+                            backgrounds.len()
+                        ),
+                        extra.to_vec(),
+                    )
+                    .with_fix("remove every background but the last", remove(extra)),
+                );
+            }
+        }
+        Rule::UnpairedPipe | Rule::DeadEndPipe => {
+            for problem in Pipes::analyze(blocks)?.problems {
+                let message = match (rule, problem.kind) {
+                    (Rule::UnpairedPipe, PipeProblemKind::EntranceWithoutExit) => {
+                        "pipe entrance with no exit"
+                    }
+                    (Rule::UnpairedPipe, PipeProblemKind::ExitWithoutEntrance) => {
+                        "pipe exit with no entrance"
+                    }
+                    (Rule::DeadEndPipe, PipeProblemKind::DeadEnd) => "pipe ends without an exit",
+                    _ => continue,
+                };
+                let (x, y) = problem.cell;
+                problems.push(Problem::new(
+                    message.to_string(),
+                    vec![y * crate::LEVEL_WIDTH + x],
+                ));
+            }
+        }
+        Rule::ToggleWithoutSwitch => {
+            for circuit in Circuits::analyze(blocks)?.circuits() {
+                if circuit.switches.is_empty() && !circuit.toggles.is_empty() {
+                    problems.push(Problem::new(
+                        "toggle blocks but no switch to flip them".to_string(),
+                        circuit.toggles.clone(),
+                    ));
+                }
+            }
+        }
+        Rule::EmptyNote => {
+            let notes = cells_where(&|block| match block {
+                Block::Note { text } => text.trim().is_empty(),
+                _ => false,
+            });
+            for note in notes {
+                problems.push(
+                    Problem::new("note without text".to_string(), vec![note])
+                        .with_fix("remove the note", remove(&[note])),
+                );
+            }
+        }
+        Rule::NoteWhitespace => {
+            for (i, block) in blocks.iter().enumerate() {
+                let text = match block {
+                    Block::Note { text } if !text.trim().is_empty() => text,
+                    _ => continue,
+                };
+                let trimmed = trim_lines(text);
+                if trimmed != *text {
+                    problems.push(
+                        Problem::new("note text has trailing whitespace".to_string(), vec![i])
+                            .with_fix(
+                                "trim the note text",
+                                vec![(i, Block::Note { text: trimmed })],
+                            ),
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(problems)
+}
+
+/// Remove whitespace from the end of every line and the end of the text
+fn trim_lines(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+    lines.join("\n").trim_end().to_string()
+}
+
+/// Apply the fixes of diagnostics to a level, returning the fixed level.
+///
+/// Fixes are applied in order. A fix that edits a cell an earlier fix already edited is skipped,
+/// so linting the result again may find more to fix.
+pub fn apply_fixes(blocks: &[Block], diagnostics: &[Diagnostic]) -> Vec<Block> {
+    let mut ret = blocks.to_vec();
+    let mut edited = vec![false; blocks.len()];
+    let index = |(x, y): (usize, usize)| {
+        Some(y * crate::LEVEL_WIDTH + x).filter(|i| x < crate::LEVEL_WIDTH && *i < blocks.len())
+    };
+
+    for fix in diagnostics
+        .iter()
+        .filter_map(|diagnostic| diagnostic.fix.as_ref())
+    {
+        let cells: Option<Vec<usize>> = fix.edits.iter().map(|edit| index(edit.cell)).collect();
+        let cells = match cells {
+            Some(cells) if cells.iter().all(|i| !edited[*i]) => cells,
+            _ => continue,
+        };
+
+        for (i, edit) in cells.into_iter().zip(fix.edits.iter()) {
+            ret[i] = edit.block.clone();
+            edited[i] = true;
+        }
+    }
+
+    ret
+}
+
+/// Get the (x, y) of a cell index
+fn cell_position(i: usize) -> (usize, usize) {
+    (i % crate::LEVEL_WIDTH, i / crate::LEVEL_WIDTH)
+}
+
+/// Errors that may occur while linting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintError {
+    InvalidLength(usize),
+}
+
+impl From<GridError> for LintError {
+    fn from(e: GridError) -> Self {
+        match e {
+            GridError::InvalidLength(len) => Self::InvalidLength(len),
+        }
+    }
+}
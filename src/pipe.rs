@@ -0,0 +1,173 @@
+use crate::{
+    block::Block,
+    grid::{
+        self,
+        components,
+        neighbours,
+        GridError,
+    },
+};
+use std::collections::VecDeque;
+
+/// A group of connected pipe cells. Cells are indexes into the level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PipeNetwork {
+    pub cells: Vec<usize>,
+    /// The PipeIn cells
+    pub entrances: Vec<usize>,
+    /// The PipeOut cells
+    pub exits: Vec<usize>,
+    /// Connected runs of PipePhase cells
+    pub phases: Vec<Vec<usize>>,
+    /// Every entrance and the exit it leads to, if there is one
+    pub pairs: Vec<(usize, Option<usize>)>,
+}
+
+impl PipeNetwork {
+    /// Get the exit an entrance leads to
+    pub fn exit_for(&self, entrance: usize) -> Option<usize> {
+        self.pairs
+            .iter()
+            .find(|(i, _)| *i == entrance)
+            .and_then(|(_, exit)| *exit)
+    }
+}
+
+/// The ways a pipe network can be broken
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PipeProblemKind {
+    /// A pipe that isn't an entrance or exit ends without leading anywhere
+    DeadEnd,
+    /// An entrance with no exit in its network
+    EntranceWithoutExit,
+    /// An exit that no entrance leads to
+    ExitWithoutEntrance,
+}
+
+/// A broken part of a pipe network
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PipeProblem {
+    pub kind: PipeProblemKind,
+    /// The (x, y) of the cell
+    pub cell: (usize, usize),
+    /// The index of the network in Pipes::networks
+    pub network: usize,
+}
+
+/// The pipe networks of a level
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pipes {
+    /// Networks in reading order of their first cell
+    pub networks: Vec<PipeNetwork>,
+    pub problems: Vec<PipeProblem>,
+}
+
+impl Pipes {
+    /// Trace the pipe networks of a level.
+    ///
+    /// Every entrance leads to the exit closest to it along the pipe. Ties go to the first exit in reading order.
+    pub fn analyze(blocks: &[Block]) -> Result<Self, GridError> {
+        grid::check_length(blocks)?;
+
+        let mut networks = Vec::new();
+        let mut problems = Vec::new();
+        let all_phases = components(blocks, |block| *block == Block::PipePhase);
+
+        for cells in components(blocks, is_pipe) {
+            let of_type = |target: Block| -> Vec<usize> {
+                cells
+                    .iter()
+                    .copied()
+                    .filter(|i| blocks[*i] == target)
+                    .collect()
+            };
+            let entrances = of_type(Block::PipeIn);
+            let exits = of_type(Block::PipeOut);
+            let phases = all_phases
+                .iter()
+                .filter(|phase| cells.contains(&phase[0]))
+                .cloned()
+                .collect();
+            let pairs: Vec<_> = entrances
+                .iter()
+                .map(|entrance| (*entrance, nearest_exit(blocks, *entrance)))
+                .collect();
+
+            let n = networks.len();
+            let mut problem = |kind, i: usize| {
+                problems.push(PipeProblem {
+                    kind,
+                    cell: (i % crate::LEVEL_WIDTH, i / crate::LEVEL_WIDTH),
+                    network: n,
+                })
+            };
+            if exits.is_empty() {
+                for i in entrances.iter() {
+                    problem(PipeProblemKind::EntranceWithoutExit, *i);
+                }
+            }
+            for i in exits.iter() {
+                if !pairs.iter().any(|(_, exit)| *exit == Some(*i)) {
+                    problem(PipeProblemKind::ExitWithoutEntrance, *i);
+                }
+            }
+            for i in cells.iter().copied() {
+                let segment = !matches!(blocks[i], Block::PipeIn | Block::PipeOut);
+                if segment && neighbours(i).filter(|n| is_pipe(&blocks[*n])).count() <= 1 {
+                    problem(PipeProblemKind::DeadEnd, i);
+                }
+            }
+
+            networks.push(PipeNetwork {
+                cells,
+                entrances,
+                exits,
+                phases,
+                pairs,
+            });
+        }
+
+        Ok(Self { networks, problems })
+    }
+
+    /// Get the exit an entrance leads to
+    pub fn exit_for(&self, entrance: usize) -> Option<usize> {
+        self.networks
+            .iter()
+            .find_map(|network| network.exit_for(entrance))
+    }
+}
+
+fn is_pipe(block: &Block) -> bool {
+    matches!(
+        block,
+        Block::PipeIn | Block::PipeOut | Block::PipePhase | Block::PipeSolid
+    )
+}
+
+/// Find the exit with the shortest path along the pipe from an entrance
+fn nearest_exit(blocks: &[Block], entrance: usize) -> Option<usize> {
+    let mut distance = vec![None; blocks.len()];
+    distance[entrance] = Some(0);
+    let mut queue = VecDeque::from(vec![entrance]);
+    let mut best: Option<(usize, usize)> = None;
+
+    while let Some(i) = queue.pop_front() {
+        let d = distance[i].unwrap_or(0);
+        if best.is_some_and(|(best_d, _)| d > best_d) {
+            break;
+        }
+        if blocks[i] == Block::PipeOut && best.is_none_or(|(_, exit)| i < exit) {
+            best = Some((d, i));
+        }
+
+        for n in neighbours(i) {
+            if distance[n].is_none() && is_pipe(&blocks[n]) {
+                distance[n] = Some(d + 1);
+                queue.push_back(n);
+            }
+        }
+    }
+
+    best.map(|(_, exit)| exit)
+}
@@ -1,9 +1,86 @@
-use crate::block::{
-    BackgroundType,
-    Block,
-    Direction,
+/// Rendering animations of levels
+pub mod animation;
+/// Rendering many levels in parallel
+pub mod batch;
+/// A bounded cache for rendered blocks
+pub mod cache;
+/// Coloring the wire and pipe networks of levels
+pub mod connectivity;
+/// Rendering the differences between two levels
+pub mod diff;
+/// Drawing primitives for annotating renders
+pub(crate) mod draw;
+/// Encoding renders to image files
+pub mod encode;
+/// Blending per-cell values over levels
+pub mod heatmap;
+/// Drawing paths and labels on top of levels
+pub mod overlay;
+/// Rendering contact sheets of many levels
+pub mod sheet;
+/// Rendering levels as text for terminals
+pub mod text;
+/// Loading block textures at runtime
+pub mod texture;
+
+pub use self::{
+    animation::{
+        Animation,
+        AnimationFormat,
+        AnimationOptions,
+    },
+    batch::{
+        BatchItem,
+        BatchProgress,
+    },
+    cache::{
+        CacheStats,
+        RenderCache,
+    },
+    connectivity::{
+        Connectivity,
+        Network,
+        PipeNetwork,
+    },
+    diff::{
+        CellChange,
+        DiffLayout,
+        DiffOptions,
+    },
+    encode::{
+        EmbeddedSource,
+        EncodeFormat,
+    },
+    heatmap::{
+        ColorRamp,
+        HeatmapOptions,
+    },
+    overlay::{
+        Overlay,
+        Path,
+        Point,
+    },
+    sheet::{
+        SheetEntry,
+        SheetLayout,
+        SheetOptions,
+    },
+    text::{
+        ColorMode,
+        TextMode,
+        TextRenderOptions,
+        TextRenderer,
+    },
+    texture::TexturePack,
+};
+use crate::{
+    block::{
+        BackgroundType,
+        Block,
+    },
+    grid::GridError,
 };
-use std::collections::HashMap;
+use std::sync::Arc;
 
 macro_rules! load_blocks {
     (
@@ -43,30 +120,74 @@ load_blocks! {
     D1
 }
 
-/// A block renderer based on the image crate
+/// A block renderer based on the image crate.
+///
+/// Rendering only needs a shared reference, so one renderer may be shared between threads.
 pub struct ImageRenderer {
-    cache: HashMap<ImageRequest, Option<image::DynamicImage>>,
+    cache: RenderCache,
+    textures: TexturePack,
 }
 
 impl ImageRenderer {
-    /// Create a new renderer
+    /// Create a new renderer with the embedded textures
     pub fn new() -> Self {
+        Self::with_textures(TexturePack::new())
+    }
+
+    /// Create a new renderer with a texture pack
+    pub fn with_textures(textures: TexturePack) -> Self {
         Self {
-            cache: HashMap::new(),
+            cache: RenderCache::default(),
+            textures,
         }
     }
 
+    /// Set the capacity of the cache, in bytes of pixel data
+    pub fn cache_capacity(self, capacity: usize) -> Self {
+        self.cache.set_capacity(capacity);
+        self
+    }
+
+    /// Get statistics for the cache
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Remove all images from the cache
+    pub fn clear_cache(&self) {
+        self.cache.clear();
+    }
+
     /// Render a level. blocks must be the right size.
     pub fn render(
-        &mut self,
+        &self,
+        blocks: &[Block],
+        options: &RenderOptions,
+    ) -> Result<image::DynamicImage, RenderError> {
+        self.render_with_background_offset(blocks, options, 0)
+    }
+
+    /// Render a level with the background scrolled down by offset pixels, wrapping around. blocks must be the right size.
+    pub fn render_with_background_offset(
+        &self,
         blocks: &[Block],
         options: &RenderOptions,
+        offset: u32,
     ) -> Result<image::DynamicImage, RenderError> {
         let len = blocks.len();
         if len != crate::LEVEL_SIZE {
             return Err(RenderError::InvalidLength(len));
         }
 
+        let layout = options.layout();
+        if layout.cells.width == 0 || layout.cells.height == 0 {
+            return Err(RenderError::EmptyViewport);
+        }
+        if layout.content_width == 0 || layout.content_height == 0 {
+            let (width, height) = options.size();
+            return Err(RenderError::InvalidSize { width, height });
+        }
+
         let mut bg = Block::Background {
             background_type: BackgroundType::Cobble,
         };
@@ -78,104 +199,142 @@ impl ImageRenderer {
             }
         }
 
+        // The background covers the whole level, so a viewport shows the matching part of it.
+        // Requesting it at whole level size shares cache entries with full renders at the same scale.
+        let (level_width, level_height) = layout.level_size();
         let req = ImageRequest {
-            w: options.width as u32,
-            h: options.height as u32,
+            w: level_width,
+            h: level_height,
             block: bg,
+            filter: options.filter.clone(),
         };
 
-        let mut base = self
+        let bg_img = self
             .get_rendered(req)
-            .ok_or(RenderError::MissingBackgroundTexture)?
-            .clone();
+            .ok_or(RenderError::MissingBackgroundTexture)?;
+        let bg_img = if !offset.is_multiple_of(level_height) {
+            Arc::new(scroll_down(&bg_img, offset))
+        } else {
+            bg_img
+        };
+        let bg_img = if layout.cells != CellRect::level() {
+            let x = (u64::from(layout.cells.x) * u64::from(level_width) / crate::LEVEL_WIDTH as u64)
+                as u32;
+            let y = (u64::from(layout.cells.y) * u64::from(level_height)
+                / crate::LEVEL_HEIGHT as u64) as u32;
+            Arc::new(bg_img.crop_imm(
+                x.min(level_width - layout.content_width),
+                y.min(level_height - layout.content_height),
+                layout.content_width,
+                layout.content_height,
+            ))
+        } else {
+            bg_img
+        };
 
-        let w = options.width as u32 / crate::LEVEL_WIDTH as u32;
-        let h = options.height as u32 / crate::LEVEL_HEIGHT as u32;
+        let mut base = if layout.is_padded() {
+            let mut base = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+                layout.width,
+                layout.height,
+                image::Rgba(options.letterbox_color),
+            ));
+            image::imageops::overlay(&mut base, &*bg_img, layout.x, layout.y);
+            base
+        } else {
+            (*bg_img).clone()
+        };
 
         for (y, row) in blocks.chunks(crate::LEVEL_WIDTH).enumerate() {
             for (x, block) in row.iter().enumerate() {
                 if !block.is_background() {
+                    let (cell_x, cell_y, w, h) = layout.cell_rect(x as u32, y as u32);
+                    if w == 0 || h == 0 {
+                        continue;
+                    }
+
                     let r = ImageRequest {
                         w,
                         h,
                         block: block.clone(),
+                        filter: options.filter.clone(),
                     };
                     if let Some(img) = self.get_rendered(r) {
-                        image::imageops::overlay(&mut base, img, w * x as u32, h * y as u32);
+                        let (dx, dy) = center_offset(&img, w, h);
+                        image::imageops::overlay(&mut base, &*img, cell_x + dx, cell_y + dy);
                     }
                 }
             }
         }
 
-        Ok(base)
+        match options.viewport {
+            Some(viewport) if layout.cells != viewport && options.margin_dim > 0 => {
+                let mut img = base.into_rgba8();
+                let viewport = viewport.clamp_to_level();
+                let cells = layout.cells;
+                for y in cells.y..cells.y + cells.height {
+                    for x in (cells.x..cells.x + cells.width).filter(|x| !viewport.contains(*x, y))
+                    {
+                        let (cell_x, cell_y, w, h) = layout.cell_rect(x, y);
+                        draw::fill_rect(
+                            &mut img,
+                            i64::from(cell_x),
+                            i64::from(cell_y),
+                            w,
+                            h,
+                            image::Rgba([0, 0, 0, options.margin_dim]),
+                        );
+                    }
+                }
+                Ok(image::DynamicImage::ImageRgba8(img))
+            }
+            _ => Ok(base),
+        }
     }
 
-    /// Get a resized image from the cache, else resize it and cache it, returning a reference.
-    pub fn get_rendered(&mut self, r: ImageRequest) -> Option<&image::DynamicImage> {
+    /// Get a resized image from the cache, else resize it and cache it.
+    pub fn get_rendered(&self, r: ImageRequest) -> Option<Arc<image::DynamicImage>> {
         self.cache
-            .entry(r.clone())
-            .or_insert_with(|| Self::generate_block_image(&r))
-            .as_ref()
+            .get_or_insert_with(&r, || self.textures.generate_block_image(&r))
     }
 
-    /// Generates a new block image. returns None if it is an empty image. TODO: Consider Error Texture
+    /// Generates a new block image from the embedded textures. returns None if it is an empty image. TODO: Consider Error Texture
     pub fn generate_block_image(r: &ImageRequest) -> Option<image::DynamicImage> {
-        let img = match &r.block {
-            Block::Background {
-                background_type: BackgroundType::Cobble,
-            } => M0,
-            Block::Background { .. } => {
-                return None;
-            }
-            Block::Block => B0,
-            Block::Dark | Block::Empty => {
-                return None;
-            }
-            Block::Exit => E0,
-            Block::Key => IK,
-            Block::Lock => BK,
-            Block::Note { .. } => NO,
-            Block::OneWayWall {
-                direction: Direction::Down,
-            } => OD,
-            Block::OneWayWall {
-                direction: Direction::Up,
-            } => OU,
-            Block::OneWayWall {
-                direction: Direction::Left,
-            } => OL,
-            Block::OneWayWall {
-                direction: Direction::Right,
-            } => OR,
-            Block::PipeIn => CI,
-            Block::PipeOut => CO,
-            Block::PipePhase => CP,
-            Block::PipeSolid => CS,
-            Block::Player => X0,
-            Block::PowerUpBurrow => P0,
-            Block::PowerUpRecall => P1,
-            Block::SecretExit => return None,
-            Block::Scaffold => D0,
-            Block::Switch => S0,
-            Block::SwitchCeiling => {
-                return None;
-            }
-            Block::ToggleBlock { solid: true } => T0,
-            Block::ToggleBlock { solid: false } => T1,
-            Block::Torch => D1,
-            Block::Wire => {
-                return None;
-            }
-        };
+        TexturePack::new().generate_block_image(r)
+    }
 
-        Some(
-            image::load_from_memory(img)
-                .expect("Valid Embedded image")
-                .resize(r.w, r.h, image::imageops::FilterType::Triangle),
-        )
+    /// Generates a new block image from the textures of this renderer. returns None if it is an empty image.
+    pub fn block_image(&self, r: &ImageRequest) -> Option<image::DynamicImage> {
+        self.textures.generate_block_image(r)
+    }
+
+    /// The textures this renderer uses
+    pub fn textures(&self) -> &TexturePack {
+        &self.textures
     }
 }
 
+/// Get the offset that centers a block image in a cell of w by h pixels
+pub(crate) fn center_offset(img: &image::DynamicImage, w: u32, h: u32) -> (u32, u32) {
+    use image::GenericImageView;
+
+    let (img_w, img_h) = img.dimensions();
+    (w.saturating_sub(img_w) / 2, h.saturating_sub(img_h) / 2)
+}
+
+/// Scroll an image down by offset pixels, wrapping around
+fn scroll_down(img: &image::DynamicImage, offset: u32) -> image::DynamicImage {
+    let src = img.to_rgba8();
+    let (width, height) = src.dimensions();
+    let offset = offset % height;
+
+    let mut dst = image::RgbaImage::new(width, height);
+    for (x, y, pixel) in src.enumerate_pixels() {
+        dst.put_pixel(x, (y + offset) % height, *pixel);
+    }
+
+    image::DynamicImage::ImageRgba8(dst)
+}
+
 impl Default for ImageRenderer {
     fn default() -> Self {
         Self::new()
@@ -186,15 +345,180 @@ impl Default for ImageRenderer {
 #[derive(Debug)]
 pub enum RenderError {
     InvalidLength(usize),
+    InvalidSize {
+        width: usize,
+        height: usize,
+    },
     MissingBackgroundTexture,
+    /// The viewport has no cells in the level
+    EmptyViewport,
+    /// A contact sheet would be too big, with the last row and column it would need
+    SheetTooLarge {
+        columns: usize,
+        rows: usize,
+    },
 }
 
 /// A "request" for block data from the cache
-#[derive(Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ImageRequest {
     pub w: u32,
     pub h: u32,
     pub block: Block,
+    pub filter: ScaleFilter,
+}
+
+/// How a level is fit into the requested size
+#[derive(Debug, Clone, PartialEq)]
+pub enum FitMode {
+    /// Fill the requested size exactly. If not 16:9, stretching will occur.
+    Stretch,
+    /// Keep blocks square and center the level, filling the rest with the letterbox color.
+    Letterbox,
+    /// Keep blocks square, shrinking the output to the level instead of padding it.
+    Contain,
+    /// Like Letterbox, but every block is the same whole number of pixels.
+    Integer,
+}
+
+/// The filter used to scale textures
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ScaleFilter {
+    /// Smooth, bilinear scaling
+    Smooth,
+    /// Nearest neighbour scaling. Keeps pixel art sharp.
+    Nearest,
+}
+
+impl ScaleFilter {
+    /// Get the image crate filter for this filter
+    pub fn as_filter_type(&self) -> image::imageops::FilterType {
+        match self {
+            Self::Smooth => image::imageops::FilterType::Triangle,
+            Self::Nearest => image::imageops::FilterType::Nearest,
+        }
+    }
+}
+
+/// Where a level is placed in a rendered image
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    /// The width of the image
+    pub width: u32,
+    /// The height of the image
+    pub height: u32,
+
+    /// The x offset of the level in the image
+    pub x: u32,
+    /// The y offset of the level in the image
+    pub y: u32,
+    /// The width of the level in the image
+    pub content_width: u32,
+    /// The height of the level in the image
+    pub content_height: u32,
+    /// The cells shown in the content area
+    pub cells: CellRect,
+}
+
+impl Layout {
+    /// Returns true if the level does not cover the whole image
+    pub fn is_padded(&self) -> bool {
+        self.content_width != self.width || self.content_height != self.height
+    }
+
+    /// Get the pixel rect of a cell as (x, y, width, height). x and y are level coordinates.
+    ///
+    /// Cell edges are rounded individually, so cells may differ by a pixel but never leave gaps.
+    /// Cells that are not shown have a size of 0.
+    pub fn cell_rect(&self, x: u32, y: u32) -> (u32, u32, u32, u32) {
+        if !self.cells.contains(x, y) {
+            return (self.x, self.y, 0, 0);
+        }
+
+        let cols = u64::from(self.cells.width);
+        let rows = u64::from(self.cells.height);
+        let (x, y) = (u64::from(x - self.cells.x), u64::from(y - self.cells.y));
+        let content_width = u64::from(self.content_width);
+        let content_height = u64::from(self.content_height);
+
+        let left = (x * content_width / cols) as u32;
+        let right = ((x + 1) * content_width / cols) as u32;
+        let top = (y * content_height / rows) as u32;
+        let bottom = ((y + 1) * content_height / rows) as u32;
+
+        (self.x + left, self.y + top, right - left, bottom - top)
+    }
+
+    /// The size the whole level would have at this scale, as (width, height). Rounded up.
+    pub fn level_size(&self) -> (u32, u32) {
+        let scale = |content: u32, cells: u32, level: usize| {
+            (u64::from(content) * level as u64).div_ceil(u64::from(cells.max(1))) as u32
+        };
+        (
+            scale(self.content_width, self.cells.width, crate::LEVEL_WIDTH),
+            scale(self.content_height, self.cells.height, crate::LEVEL_HEIGHT),
+        )
+    }
+
+    /// The width of a cell in pixels
+    pub fn cell_width(&self) -> f32 {
+        self.content_width as f32 / self.cells.width as f32
+    }
+
+    /// The height of a cell in pixels
+    pub fn cell_height(&self) -> f32 {
+        self.content_height as f32 / self.cells.height as f32
+    }
+}
+
+/// A rectangle of cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CellRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl CellRect {
+    /// Make a new rectangle
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// The whole level
+    pub fn level() -> Self {
+        Self::new(0, 0, crate::LEVEL_WIDTH as u32, crate::LEVEL_HEIGHT as u32)
+    }
+
+    /// Returns true if a cell is in this rectangle
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && y >= self.y && x - self.x < self.width && y - self.y < self.height
+    }
+
+    /// Grow this rectangle by margin cells on every side, then clamp it to the level
+    pub fn expand(&self, margin: u32) -> Self {
+        let x = self.x.saturating_sub(margin);
+        let y = self.y.saturating_sub(margin);
+        let right = self.x.saturating_add(self.width).saturating_add(margin);
+        let bottom = self.y.saturating_add(self.height).saturating_add(margin);
+        Self::new(x, y, right - x, bottom - y).clamp_to_level()
+    }
+
+    /// Remove the parts of this rectangle outside of the level
+    pub fn clamp_to_level(&self) -> Self {
+        let level = Self::level();
+        let x = self.x.min(level.width);
+        let y = self.y.min(level.height);
+        let right = self.x.saturating_add(self.width).min(level.width);
+        let bottom = self.y.saturating_add(self.height).min(level.height);
+        Self::new(x, y, right - x, bottom - y)
+    }
 }
 
 /// Options for rendering
@@ -202,6 +526,17 @@ pub struct ImageRequest {
 pub struct RenderOptions {
     pub width: usize,
     pub height: usize,
+    fit: FitMode,
+    filter: ScaleFilter,
+    letterbox_color: [u8; 4],
+    /// The cells to render. None renders the whole level.
+    viewport: Option<CellRect>,
+    /// Cells of context shown around the viewport
+    margin: u32,
+    /// The opacity of the black drawn over the margin
+    margin_dim: u8,
+    /// Pixels per cell. Overrides width and height when set.
+    cell_size: Option<usize>,
 }
 
 impl RenderOptions {
@@ -210,20 +545,154 @@ impl RenderOptions {
         Self {
             width: 1920,
             height: 1080,
+            fit: FitMode::Stretch,
+            filter: ScaleFilter::Smooth,
+            letterbox_color: [0, 0, 0, 255],
+            viewport: None,
+            margin: 0,
+            margin_dim: 128,
+            cell_size: None,
         }
     }
 
-    /// Requested Width. Note: if not 16:9, stretching will occur
+    /// Requested Width. Replaces a requested cell_size. Note: if not 16:9, stretching will occur in the Stretch fit mode
     pub fn width(mut self, width: usize) -> Self {
         self.width = width;
+        self.cell_size = None;
         self
     }
 
-    /// Requested Height. Note: if not 16:9, stretching will occur
+    /// Requested Height. Replaces a requested cell_size. Note: if not 16:9, stretching will occur in the Stretch fit mode
     pub fn height(mut self, height: usize) -> Self {
         self.height = height;
+        self.cell_size = None;
+        self
+    }
+
+    /// Request a size of cell_size pixels per block, whatever the viewport and margin are
+    pub fn cell_size(mut self, cell_size: usize) -> Self {
+        self.cell_size = Some(cell_size);
         self
     }
+
+    /// How the level is fit into the requested size
+    pub fn fit(mut self, fit: FitMode) -> Self {
+        self.fit = fit;
+        self
+    }
+
+    /// The filter used to scale textures
+    pub fn filter(mut self, filter: ScaleFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// The color of padding in the Letterbox and Integer fit modes, as rgba
+    pub fn letterbox_color(mut self, letterbox_color: [u8; 4]) -> Self {
+        self.letterbox_color = letterbox_color;
+        self
+    }
+
+    /// Only render a rectangle of cells
+    pub fn viewport(mut self, viewport: CellRect) -> Self {
+        self.viewport = Some(viewport);
+        self
+    }
+
+    /// Show margin cells of context around the viewport, dimmed by margin_dim
+    pub fn margin(mut self, margin: u32) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    /// The opacity of the black drawn over the margin
+    pub fn margin_dim(mut self, margin_dim: u8) -> Self {
+        self.margin_dim = margin_dim;
+        self
+    }
+
+    /// Get the cells that will be rendered: the viewport and its margin, or the whole level
+    pub fn visible_cells(&self) -> CellRect {
+        match self.viewport {
+            Some(viewport) => viewport.clamp_to_level().expand(self.margin),
+            None => CellRect::level(),
+        }
+    }
+
+    /// Integer scaling: every block is cell_size pixels, and textures are scaled with nearest neighbour.
+    ///
+    /// Textures are scaled from their own size, which is usually not a whole multiple of cell_size.
+    pub fn integer_scale(self, cell_size: usize) -> Self {
+        self.cell_size(cell_size)
+            .fit(FitMode::Integer)
+            .filter(ScaleFilter::Nearest)
+    }
+
+    /// Get the requested size of the image as (width, height), with the cell size applied
+    pub fn size(&self) -> (usize, usize) {
+        match self.cell_size {
+            Some(cell_size) => {
+                let cells = self.visible_cells();
+                (
+                    cell_size * cells.width as usize,
+                    cell_size * cells.height as usize,
+                )
+            }
+            None => (self.width, self.height),
+        }
+    }
+
+    /// Calculate where the level will be placed in the rendered image
+    pub fn layout(&self) -> Layout {
+        let (width, height) = self.size();
+        let (width, height) = (width as u32, height as u32);
+        let cells = self.visible_cells();
+        let cols = cells.width.max(1);
+        let rows = cells.height.max(1);
+
+        let (content_width, content_height) = match self.fit {
+            FitMode::Stretch => (width, height),
+            FitMode::Letterbox | FitMode::Contain => {
+                // Compare width / cols with height / rows without floats
+                if u64::from(width) * u64::from(rows) <= u64::from(height) * u64::from(cols) {
+                    (
+                        width,
+                        (u64::from(width) * u64::from(rows) / u64::from(cols)) as u32,
+                    )
+                } else {
+                    (
+                        (u64::from(height) * u64::from(cols) / u64::from(rows)) as u32,
+                        height,
+                    )
+                }
+            }
+            FitMode::Integer => {
+                let cell_size = (width / cols).min(height / rows);
+                (cell_size * cols, cell_size * rows)
+            }
+        };
+
+        match self.fit {
+            FitMode::Contain => Layout {
+                width: content_width,
+                height: content_height,
+                x: 0,
+                y: 0,
+                content_width,
+                content_height,
+                cells,
+            },
+            FitMode::Stretch | FitMode::Letterbox | FitMode::Integer => Layout {
+                width,
+                height,
+                x: (width - content_width) / 2,
+                y: (height - content_height) / 2,
+                content_width,
+                content_height,
+                cells,
+            },
+        }
+    }
 }
 
 impl Default for RenderOptions {
@@ -231,3 +700,11 @@ impl Default for RenderOptions {
         Self::new()
     }
 }
+
+impl From<GridError> for RenderError {
+    fn from(e: GridError) -> Self {
+        match e {
+            GridError::InvalidLength(len) => Self::InvalidLength(len),
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use crate::{
+    block::Block,
+    render::{
+        encode::png_chunks,
+        ImageRenderer,
+        RenderError,
+        RenderOptions,
+    },
+};
+use std::convert::TryInto;
+
+/// What an animation shows
+#[derive(Debug, Clone)]
+pub enum Animation<'a> {
+    /// Alternate between the level and the level with every toggle block flipped
+    Toggle(&'a [Block]),
+    /// Scroll the background of a level down, like a waterfall.
+    /// If the renderer has no texture for the level's background, the default background is used.
+    Background(&'a [Block]),
+    /// Play back a sequence of level snapshots
+    Snapshots(&'a [Vec<Block>]),
+}
+
+/// The file formats animations can be encoded as
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnimationFormat {
+    Gif,
+    Apng,
+}
+
+/// Options for rendering animations
+#[derive(Debug)]
+pub struct AnimationOptions {
+    pub format: AnimationFormat,
+    /// The time every frame is shown, in milliseconds
+    pub delay_ms: u32,
+    /// Per frame delays in milliseconds, overriding delay_ms. Frames past the end use delay_ms.
+    pub frame_delays: Vec<u32>,
+    /// The number of times to play the animation. 0 loops forever.
+    pub loop_count: u16,
+    /// The number of frames it takes the background to scroll by a whole level height
+    pub background_frames: u32,
+}
+
+impl AnimationOptions {
+    /// Default AnimationOptions.
+    pub fn new() -> Self {
+        Self {
+            format: AnimationFormat::Gif,
+            delay_ms: 500,
+            frame_delays: Vec::new(),
+            loop_count: 0,
+            background_frames: 24,
+        }
+    }
+
+    /// The file format
+    pub fn format(mut self, format: AnimationFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// The time every frame is shown, in milliseconds
+    pub fn delay_ms(mut self, delay_ms: u32) -> Self {
+        self.delay_ms = delay_ms;
+        self
+    }
+
+    /// Per frame delays in milliseconds, overriding delay_ms
+    pub fn frame_delays(mut self, frame_delays: Vec<u32>) -> Self {
+        self.frame_delays = frame_delays;
+        self
+    }
+
+    /// The number of times to play the animation. 0 loops forever.
+    pub fn loop_count(mut self, loop_count: u16) -> Self {
+        self.loop_count = loop_count;
+        self
+    }
+
+    /// The number of frames it takes the background to scroll by a whole level height
+    pub fn background_frames(mut self, background_frames: u32) -> Self {
+        self.background_frames = background_frames;
+        self
+    }
+
+    /// Get the delay of a frame, in milliseconds
+    pub fn frame_delay(&self, frame: usize) -> u32 {
+        self.frame_delays
+            .get(frame)
+            .copied()
+            .unwrap_or(self.delay_ms)
+    }
+}
+
+impl Default for AnimationOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Flip every toggle block of a level
+pub fn toggled(blocks: &[Block]) -> Vec<Block> {
+    blocks
+        .iter()
+        .map(|block| match block {
+            Block::ToggleBlock { solid } => Block::ToggleBlock { solid: !solid },
+            block => block.clone(),
+        })
+        .collect()
+}
+
+impl ImageRenderer {
+    /// Render the frames of an animation
+    pub fn render_animation_frames(
+        &self,
+        animation: &Animation,
+        options: &RenderOptions,
+        animation_options: &AnimationOptions,
+    ) -> Result<Vec<image::RgbaImage>, RenderError> {
+        let frames = match animation {
+            Animation::Toggle(blocks) => {
+                let toggled = toggled(blocks);
+                vec![
+                    self.render(blocks, options)?,
+                    self.render(&toggled, options)?,
+                ]
+            }
+            Animation::Background(blocks) => {
+                // Only cobble is embedded, so backgrounds without a texture scroll the default one instead
+                let missing = blocks
+                    .iter()
+                    .any(|block| block.is_background() && self.textures().get(block).is_none());
+                let fallback: Vec<Block>;
+                let blocks = if missing {
+                    fallback = blocks
+                        .iter()
+                        .map(|block| match block {
+                            block if block.is_background() => Block::Empty,
+                            block => block.clone(),
+                        })
+                        .collect();
+                    &fallback
+                } else {
+                    *blocks
+                };
+
+                let (_, height) = options.layout().level_size();
+                let frames = animation_options.background_frames.max(1);
+                (0..frames)
+                    .map(|i| {
+                        let offset = (u64::from(height) * u64::from(i) / u64::from(frames)) as u32;
+                        self.render_with_background_offset(blocks, options, offset)
+                    })
+                    .collect::<Result<_, _>>()?
+            }
+            Animation::Snapshots(levels) => self
+                .render_all(levels, options)
+                .into_iter()
+                .collect::<Result<_, _>>()?,
+        };
+
+        Ok(frames.into_iter().map(|frame| frame.into_rgba8()).collect())
+    }
+
+    /// Render an animation, encoded in the requested format
+    pub fn render_animation(
+        &self,
+        animation: &Animation,
+        options: &RenderOptions,
+        animation_options: &AnimationOptions,
+    ) -> Result<Vec<u8>, AnimationError> {
+        let frames = self
+            .render_animation_frames(animation, options, animation_options)
+            .map_err(AnimationError::Render)?;
+
+        match animation_options.format {
+            AnimationFormat::Gif => encode_gif(frames, animation_options),
+            AnimationFormat::Apng => encode_apng(&frames, animation_options),
+        }
+    }
+}
+
+/// Encode frames as an animated gif. Gif delays are in 10ms steps, so delays are rounded.
+pub fn encode_gif(
+    frames: Vec<image::RgbaImage>,
+    options: &AnimationOptions,
+) -> Result<Vec<u8>, AnimationError> {
+    if frames.is_empty() {
+        return Err(AnimationError::NoFrames);
+    }
+
+    let mut ret = Vec::new();
+    {
+        let mut encoder = image::gif::GifEncoder::new(&mut ret);
+        // The loop extension counts repeats after the first play, and playing once needs none
+        match options.loop_count {
+            0 => encoder.set_repeat(image::gif::Repeat::Infinite)?,
+            1 => {}
+            n => encoder.set_repeat(image::gif::Repeat::Finite(n - 1))?,
+        }
+
+        encoder.encode_frames(frames.into_iter().enumerate().map(|(i, frame)| {
+            let delay = image::Delay::from_numer_denom_ms(options.frame_delay(i), 1);
+            image::Frame::from_parts(frame, 0, 0, delay)
+        }))?;
+    }
+
+    Ok(ret)
+}
+
+/// Encode frames as an animated png. All frames must be the same size.
+pub fn encode_apng(
+    frames: &[image::RgbaImage],
+    options: &AnimationOptions,
+) -> Result<Vec<u8>, AnimationError> {
+    let (width, height) = frames.first().ok_or(AnimationError::NoFrames)?.dimensions();
+    if frames
+        .iter()
+        .any(|frame| frame.dimensions() != (width, height))
+    {
+        return Err(AnimationError::FrameSizeMismatch);
+    }
+
+    let mut ret = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut ret, width, height);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+
+        let mut actl = Vec::with_capacity(8);
+        actl.extend_from_slice(&(frames.len() as u32).to_be_bytes());
+        actl.extend_from_slice(&u32::from(options.loop_count).to_be_bytes());
+        writer.write_chunk(*b"acTL", &actl)?;
+
+        let mut sequence: u32 = 0;
+        for (i, frame) in frames.iter().enumerate() {
+            let delay: u16 = options.frame_delay(i).try_into().unwrap_or(u16::MAX);
+            let mut fctl = Vec::with_capacity(26);
+            fctl.extend_from_slice(&sequence.to_be_bytes());
+            fctl.extend_from_slice(&width.to_be_bytes());
+            fctl.extend_from_slice(&height.to_be_bytes());
+            fctl.extend_from_slice(&0u32.to_be_bytes()); // x offset
+            fctl.extend_from_slice(&0u32.to_be_bytes()); // y offset
+            fctl.extend_from_slice(&delay.to_be_bytes());
+            fctl.extend_from_slice(&1000u16.to_be_bytes()); // delay is in 1/1000 seconds
+            fctl.push(0); // dispose op: none
+            fctl.push(0); // blend op: source
+            writer.write_chunk(*b"fcTL", &fctl)?;
+            sequence += 1;
+
+            if i == 0 {
+                // The first frame doubles as the still image
+                writer.write_image_data(frame)?;
+            } else {
+                let mut fdat = sequence.to_be_bytes().to_vec();
+                fdat.extend(compressed_image_data(frame)?);
+                writer.write_chunk(*b"fdAT", &fdat)?;
+                sequence += 1;
+            }
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Get the compressed, filtered image data of a frame, as it would be in the IDAT chunks of a png
+fn compressed_image_data(frame: &image::RgbaImage) -> Result<Vec<u8>, AnimationError> {
+    let mut png = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png, frame.width(), frame.height());
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(frame)?;
+    }
+
+    Ok(png_chunks(&png)
+        .filter(|(kind, _)| *kind == b"IDAT")
+        .flat_map(|(_, data)| data.iter().copied())
+        .collect())
+}
+
+/// Errors that may occur while rendering an animation
+#[derive(Debug)]
+pub enum AnimationError {
+    Render(RenderError),
+    Image(image::ImageError),
+    Png(png::EncodingError),
+
+    NoFrames,
+    FrameSizeMismatch,
+}
+
+impl From<image::ImageError> for AnimationError {
+    fn from(e: image::ImageError) -> Self {
+        Self::Image(e)
+    }
+}
+
+impl From<png::EncodingError> for AnimationError {
+    fn from(e: png::EncodingError) -> Self {
+        Self::Png(e)
+    }
+}
@@ -0,0 +1,83 @@
+use crate::{
+    block::Block,
+    render::{
+        ImageRenderer,
+        RenderError,
+        RenderOptions,
+    },
+};
+use rayon::prelude::*;
+use std::sync::mpsc;
+
+/// How far along a batch is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchProgress {
+    /// The number of levels that are done, including the current one
+    pub completed: usize,
+    /// The number of levels in the batch
+    pub total: usize,
+}
+
+/// A rendered level from a batch
+#[derive(Debug)]
+pub struct BatchItem {
+    /// The index of the level in the batch
+    pub index: usize,
+    /// The rendered level. An error here does not stop the rest of the batch.
+    pub result: Result<image::DynamicImage, RenderError>,
+    pub progress: BatchProgress,
+}
+
+impl ImageRenderer {
+    /// Render many levels in parallel, sharing this renderer's textures and cache.
+    ///
+    /// on_item is called on the current thread with each level as soon as it is done, in completion order.
+    /// This returns when every level has been rendered.
+    pub fn render_batch<L, F>(&self, levels: &[L], options: &RenderOptions, mut on_item: F)
+    where
+        L: AsRef<[Block]> + Sync,
+        F: FnMut(BatchItem),
+    {
+        let total = levels.len();
+        let (tx, rx) = mpsc::channel();
+
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                levels
+                    .par_iter()
+                    .enumerate()
+                    .for_each_with(tx, |tx, (index, level)| {
+                        let result = self.render(level.as_ref(), options);
+                        // The receiver lives until every sender is gone
+                        let _ = tx.send((index, result));
+                    });
+            });
+
+            for (completed, (index, result)) in rx.iter().enumerate() {
+                on_item(BatchItem {
+                    index,
+                    result,
+                    progress: BatchProgress {
+                        completed: completed + 1,
+                        total,
+                    },
+                });
+            }
+        });
+    }
+
+    /// Render many levels in parallel, returning the results in the same order as the levels
+    pub fn render_all<L>(
+        &self,
+        levels: &[L],
+        options: &RenderOptions,
+    ) -> Vec<Result<image::DynamicImage, RenderError>>
+    where
+        L: AsRef<[Block]> + Sync,
+    {
+        levels
+            .par_iter()
+            .map(|level| self.render(level.as_ref(), options))
+            .collect()
+    }
+}
@@ -0,0 +1,188 @@
+use crate::render::ImageRequest;
+use std::{
+    collections::{
+        BTreeMap,
+        HashMap,
+    },
+    sync::{
+        Arc,
+        Mutex,
+        MutexGuard,
+        PoisonError,
+    },
+};
+
+/// The default capacity of a cache, in bytes
+pub const DEFAULT_CAPACITY: usize = 128 * 1024 * 1024;
+
+/// A size bounded, thread safe cache of rendered block images. The least recently used images are evicted first.
+#[derive(Debug)]
+pub struct RenderCache {
+    inner: Mutex<CacheInner>,
+}
+
+impl RenderCache {
+    /// Make a new cache that holds at most capacity bytes of pixel data
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(CacheInner {
+                entries: HashMap::new(),
+                lru: BTreeMap::new(),
+                tick: 0,
+                stats: CacheStats {
+                    capacity,
+                    ..CacheStats::default()
+                },
+            }),
+        }
+    }
+
+    /// Get an image from the cache, else generate it with f and cache it.
+    ///
+    /// The lock is not held while f runs, so other threads may use the cache in the meantime.
+    pub fn get_or_insert_with<F>(&self, r: &ImageRequest, f: F) -> Option<Arc<image::DynamicImage>>
+    where
+        F: FnOnce() -> Option<image::DynamicImage>,
+    {
+        if let Some(img) = self.lock().get(r) {
+            return img;
+        }
+
+        let img = f().map(Arc::new);
+        self.lock().insert(r.clone(), img)
+    }
+
+    /// Get statistics for this cache
+    pub fn stats(&self) -> CacheStats {
+        self.lock().stats.clone()
+    }
+
+    /// Change the capacity of this cache, evicting images if needed
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut inner = self.lock();
+        inner.stats.capacity = capacity;
+        inner.evict(0);
+    }
+
+    /// Remove all images from this cache. Hit, miss, and eviction counts are kept.
+    pub fn clear(&self) {
+        let mut inner = self.lock();
+        inner.entries.clear();
+        inner.lru.clear();
+        inner.stats.entries = 0;
+        inner.stats.bytes = 0;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CacheInner> {
+        // The cache is never left in an invalid state, so poisoning can be ignored.
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for RenderCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+/// Statistics for a cache
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CacheStats {
+    /// The number of lookups that found an image
+    pub hits: u64,
+    /// The number of lookups that had to generate an image
+    pub misses: u64,
+    /// The number of images removed to stay under capacity
+    pub evictions: u64,
+    /// The number of cached images
+    pub entries: usize,
+    /// The number of bytes of pixel data cached
+    pub bytes: usize,
+    /// The maximum number of bytes of pixel data that may be cached
+    pub capacity: usize,
+}
+
+#[derive(Debug)]
+struct CacheInner {
+    entries: HashMap<ImageRequest, CacheEntry>,
+    /// Requests, ordered by last use
+    lru: BTreeMap<u64, ImageRequest>,
+    tick: u64,
+    stats: CacheStats,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    img: Option<Arc<image::DynamicImage>>,
+    last_used: u64,
+    size: usize,
+}
+
+impl CacheInner {
+    /// Look up an image, marking it as used. Returns None on a miss.
+    fn get(&mut self, r: &ImageRequest) -> Option<Option<Arc<image::DynamicImage>>> {
+        self.tick += 1;
+        let tick = self.tick;
+        match self.entries.get_mut(r) {
+            Some(entry) => {
+                self.lru.remove(&entry.last_used);
+                self.lru.insert(tick, r.clone());
+                entry.last_used = tick;
+                self.stats.hits += 1;
+                Some(entry.img.clone())
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Insert an image, unless another thread already did. Returns the cached image.
+    fn insert(
+        &mut self,
+        r: ImageRequest,
+        img: Option<Arc<image::DynamicImage>>,
+    ) -> Option<Arc<image::DynamicImage>> {
+        if let Some(entry) = self.entries.get(&r) {
+            return entry.img.clone();
+        }
+
+        let size = img.as_ref().map_or(0, |img| img.as_bytes().len());
+        if size > self.stats.capacity {
+            return img;
+        }
+        self.evict(size);
+
+        self.tick += 1;
+        self.lru.insert(self.tick, r.clone());
+        self.entries.insert(
+            r,
+            CacheEntry {
+                img: img.clone(),
+                last_used: self.tick,
+                size,
+            },
+        );
+        self.stats.entries += 1;
+        self.stats.bytes += size;
+
+        img
+    }
+
+    /// Evict images until there is room for extra bytes
+    fn evict(&mut self, extra: usize) {
+        while self.stats.bytes + extra > self.stats.capacity {
+            let tick = match self.lru.keys().next() {
+                Some(tick) => *tick,
+                None => break,
+            };
+            let r = self.lru.remove(&tick).expect("Valid LRU entry");
+            let entry = self.entries.remove(&r).expect("Valid cache entry");
+
+            self.stats.entries -= 1;
+            self.stats.bytes -= entry.size;
+            self.stats.evictions += 1;
+        }
+    }
+}
@@ -0,0 +1,159 @@
+pub use crate::pipe::PipeNetwork;
+use crate::{
+    block::Block,
+    circuit::Circuits,
+    grid::{
+        neighbours,
+        GridError,
+    },
+    pipe::Pipes,
+    render::{
+        draw,
+        overlay::{
+            Overlay,
+            Path,
+            Point,
+        },
+        ImageRenderer,
+        Layout,
+        RenderError,
+        RenderOptions,
+    },
+};
+
+/// A group of cells that work together. Cells are indexes into the level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Network {
+    pub cells: Vec<usize>,
+}
+
+/// The wire and pipe networks of a level
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Connectivity {
+    /// The cells of every circuit, in the order of Circuits::circuits.
+    /// Unwired switches and toggle blocks share a circuit, since they flip together.
+    pub wires: Vec<Network>,
+    pub pipes: Vec<PipeNetwork>,
+}
+
+impl Connectivity {
+    /// Find the networks of a level
+    pub fn analyze(blocks: &[Block]) -> Result<Self, GridError> {
+        let wires = Circuits::analyze(blocks)?
+            .circuits()
+            .iter()
+            .map(|circuit| Network {
+                cells: circuit.cells.clone(),
+            })
+            .collect();
+        let pipes = Pipes::analyze(blocks)?.networks;
+
+        Ok(Self { wires, pipes })
+    }
+}
+
+/// Get a distinct color for a network index
+pub fn network_color(index: usize) -> [u8; 3] {
+    // Golden angle hue steps keep neighbouring indexes far apart
+    let hue = (index as f32 * 137.508) % 360.0;
+    let c = 0.85;
+    let x = c * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let m = 0.15;
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+
+    [
+        ((r + m) * 255.0) as u8,
+        ((g + m) * 255.0) as u8,
+        ((b + m) * 255.0) as u8,
+    ]
+}
+
+impl ImageRenderer {
+    /// Render a level with every wire and pipe network colored distinctly.
+    ///
+    /// Wires are drawn as lines between connected cells.
+    /// Switches and toggle blocks are tinted and outlined in the color of their network.
+    /// Pipe networks are tinted, with arrows from every entrance to the exit it leads to.
+    pub fn render_connectivity(
+        &self,
+        blocks: &[Block],
+        options: &RenderOptions,
+    ) -> Result<image::DynamicImage, RenderError> {
+        let mut img = self.render(blocks, options)?.into_rgba8();
+        let layout = options.layout();
+        let cell_size = layout.cell_width();
+        let connectivity = Connectivity::analyze(blocks)?;
+
+        for (index, network) in connectivity.wires.iter().enumerate() {
+            let [r, g, b] = network_color(index);
+            let color = image::Rgba([r, g, b, 255]);
+            for i in network.cells.iter().copied() {
+                if blocks[i] != Block::Wire {
+                    draw_cell(&mut img, &layout, i, [r, g, b]);
+                }
+
+                // Link every cell to the cells right of and below it, so every edge is drawn once
+                for n in neighbours(i).filter(|n| *n > i && network.cells.contains(n)) {
+                    draw::line(
+                        &mut img,
+                        cell_center(i).to_pixel(&layout),
+                        cell_center(n).to_pixel(&layout),
+                        (cell_size * 0.2).max(1.0),
+                        color,
+                    );
+                }
+            }
+        }
+
+        let mut overlay = Overlay::new();
+        for (index, network) in connectivity.pipes.iter().enumerate() {
+            let [r, g, b] = network_color(connectivity.wires.len() + index);
+            for i in network.cells.iter().copied() {
+                draw_cell(&mut img, &layout, i, [r, g, b]);
+            }
+            for (entrance, exit) in network.pairs.iter() {
+                if let Some(exit) = exit {
+                    overlay = overlay.path(
+                        Path::new(cell_center(*entrance))
+                            .line_to(cell_center(*exit))
+                            .color([r, g, b, 255])
+                            .width(0.1),
+                    );
+                }
+            }
+        }
+        overlay.draw(&mut img, &layout);
+
+        Ok(image::DynamicImage::ImageRgba8(img))
+    }
+}
+
+/// Tint and outline a cell of a network
+fn draw_cell(img: &mut image::RgbaImage, layout: &Layout, i: usize, color: [u8; 3]) {
+    let [r, g, b] = color;
+    let (x, y) = (i % crate::LEVEL_WIDTH, i / crate::LEVEL_WIDTH);
+    let (cell_x, cell_y, w, h) = layout.cell_rect(x as u32, y as u32);
+    let (cell_x, cell_y) = (i64::from(cell_x), i64::from(cell_y));
+
+    draw::fill_rect(img, cell_x, cell_y, w, h, image::Rgba([r, g, b, 96]));
+    draw::outline_rect(
+        img,
+        cell_x,
+        cell_y,
+        w,
+        h,
+        (w.min(h) / 10).max(1),
+        image::Rgba([r, g, b, 255]),
+    );
+}
+
+fn cell_center(i: usize) -> Point {
+    Point::cell(i % crate::LEVEL_WIDTH, i / crate::LEVEL_WIDTH)
+}
@@ -0,0 +1,196 @@
+use crate::{
+    block::Block,
+    render::{
+        draw,
+        ImageRenderer,
+        Layout,
+        RenderError,
+        RenderOptions,
+    },
+};
+
+/// How a cell changed between two levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CellChange {
+    Unchanged,
+    /// The cell was empty and now has a block
+    Added,
+    /// The cell had a block and is now empty
+    Removed,
+    /// The cell has a different block
+    Changed,
+}
+
+/// Compare two levels cell by cell. Both must be the same length.
+pub fn diff_cells(old: &[Block], new: &[Block]) -> Vec<CellChange> {
+    old.iter()
+        .zip(new.iter())
+        .map(|(old, new)| match (old.is_empty(), new.is_empty()) {
+            _ if old == new => CellChange::Unchanged,
+            (true, false) => CellChange::Added,
+            (false, true) => CellChange::Removed,
+            _ => CellChange::Changed,
+        })
+        .collect()
+}
+
+/// How the levels of a diff are arranged
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffLayout {
+    /// Only the new level, with every change outlined
+    Overlay,
+    /// The old level on the left and the new level on the right, with changes outlined in both
+    SideBySide { spacing: u32 },
+}
+
+/// Options for rendering a diff
+#[derive(Debug)]
+pub struct DiffOptions {
+    pub layout: DiffLayout,
+    /// The opacity of the black drawn over unchanged cells
+    pub dim: u8,
+    pub added_color: [u8; 4],
+    pub removed_color: [u8; 4],
+    pub changed_color: [u8; 4],
+    /// The width of outlines in pixels. 0 picks one based on the cell size.
+    pub outline_width: u32,
+}
+
+impl DiffOptions {
+    /// Default DiffOptions.
+    pub fn new() -> Self {
+        Self {
+            layout: DiffLayout::Overlay,
+            dim: 160,
+            added_color: [0, 220, 0, 255],
+            removed_color: [220, 0, 0, 255],
+            changed_color: [255, 200, 0, 255],
+            outline_width: 0,
+        }
+    }
+
+    /// How the levels are arranged
+    pub fn layout(mut self, layout: DiffLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// The opacity of the black drawn over unchanged cells
+    pub fn dim(mut self, dim: u8) -> Self {
+        self.dim = dim;
+        self
+    }
+
+    /// The outline colors for added, removed, and changed cells
+    pub fn colors(mut self, added: [u8; 4], removed: [u8; 4], changed: [u8; 4]) -> Self {
+        self.added_color = added;
+        self.removed_color = removed;
+        self.changed_color = changed;
+        self
+    }
+
+    /// The width of outlines in pixels. 0 picks one based on the cell size.
+    pub fn outline_width(mut self, outline_width: u32) -> Self {
+        self.outline_width = outline_width;
+        self
+    }
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ImageRenderer {
+    /// Render the differences between two levels. Both must be the right size.
+    pub fn render_diff(
+        &self,
+        old: &[Block],
+        new: &[Block],
+        options: &RenderOptions,
+        diff_options: &DiffOptions,
+    ) -> Result<image::DynamicImage, RenderError> {
+        if old.len() != crate::LEVEL_SIZE {
+            return Err(RenderError::InvalidLength(old.len()));
+        }
+
+        let new_img = self.render(new, options)?;
+        let old_img = match diff_options.layout {
+            DiffLayout::Overlay => None,
+            DiffLayout::SideBySide { .. } => Some(self.render(old, options)?),
+        };
+
+        let changes = diff_cells(old, new);
+        let layout = options.layout();
+
+        // Without the old level next to it, removed cells are only visible on the new one
+        let overlay = diff_options.layout == DiffLayout::Overlay;
+        let mut new_img = new_img.into_rgba8();
+        annotate(&mut new_img, &layout, &changes, diff_options, |change| {
+            overlay || change != CellChange::Removed
+        });
+
+        match (old_img, &diff_options.layout) {
+            (Some(old_img), DiffLayout::SideBySide { spacing }) => {
+                let mut old_img = old_img.into_rgba8();
+                annotate(&mut old_img, &layout, &changes, diff_options, |change| {
+                    change != CellChange::Added
+                });
+
+                let mut img = image::RgbaImage::from_pixel(
+                    layout.width * 2 + spacing,
+                    layout.height,
+                    image::Rgba(options.letterbox_color),
+                );
+                image::imageops::overlay(&mut img, &old_img, 0, 0);
+                image::imageops::overlay(&mut img, &new_img, layout.width + spacing, 0);
+                Ok(image::DynamicImage::ImageRgba8(img))
+            }
+            _ => Ok(image::DynamicImage::ImageRgba8(new_img)),
+        }
+    }
+}
+
+/// Dim unchanged cells and outline the changes for which show returns true
+fn annotate<F>(
+    img: &mut image::RgbaImage,
+    layout: &Layout,
+    changes: &[CellChange],
+    options: &DiffOptions,
+    show: F,
+) where
+    F: Fn(CellChange) -> bool,
+{
+    for (i, change) in changes.iter().enumerate() {
+        let x = (i % crate::LEVEL_WIDTH) as u32;
+        let y = (i / crate::LEVEL_WIDTH) as u32;
+        let (cell_x, cell_y, w, h) = layout.cell_rect(x, y);
+        let (cell_x, cell_y) = (i64::from(cell_x), i64::from(cell_y));
+
+        let color = match change {
+            CellChange::Unchanged => {
+                draw::fill_rect(
+                    img,
+                    cell_x,
+                    cell_y,
+                    w,
+                    h,
+                    image::Rgba([0, 0, 0, options.dim]),
+                );
+                continue;
+            }
+            _ if !show(*change) => continue,
+            CellChange::Added => options.added_color,
+            CellChange::Removed => options.removed_color,
+            CellChange::Changed => options.changed_color,
+        };
+
+        let thickness = if options.outline_width > 0 {
+            options.outline_width
+        } else {
+            (w.min(h) / 8).max(1)
+        };
+        draw::outline_rect(img, cell_x, cell_y, w, h, thickness, image::Rgba(color));
+    }
+}
@@ -0,0 +1,268 @@
+use image::{
+    Rgba,
+    RgbaImage,
+};
+
+/// The height of a glyph, in font pixels
+pub const GLYPH_HEIGHT: u32 = 7;
+/// The width of a glyph, in font pixels
+pub const GLYPH_WIDTH: u32 = 5;
+/// The horizontal distance between the starts of two glyphs, in font pixels
+pub const GLYPH_ADVANCE: u32 = GLYPH_WIDTH + 1;
+
+/// Get the rows of a glyph. The highest of the 5 used bits is the leftmost pixel. Lowercase is drawn as uppercase.
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        ' ' => [0x00; 7],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+        '\'' => [0x04, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '<' => [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02],
+        '>' => [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08],
+        '#' => [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        // '?' and everything without a glyph
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
+
+/// Get the size of text in pixels, as (width, height)
+pub fn text_size(text: &str, scale: u32) -> (u32, u32) {
+    let chars = text.chars().count() as u32;
+    if chars == 0 {
+        return (0, 0);
+    }
+
+    ((chars * GLYPH_ADVANCE - 1) * scale, GLYPH_HEIGHT * scale)
+}
+
+/// Draw text with its top left corner at x, y. Pixels outside of the image are skipped.
+pub fn text(img: &mut RgbaImage, x: i64, y: i64, text: &str, scale: u32, color: Rgba<u8>) {
+    let scale = i64::from(scale);
+    for (i, c) in text.chars().enumerate() {
+        let glyph_x = x + i as i64 * i64::from(GLYPH_ADVANCE) * scale;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                    fill_rect(
+                        img,
+                        glyph_x + i64::from(col) * scale,
+                        y + row as i64 * scale,
+                        scale as u32,
+                        scale as u32,
+                        color,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Draw text on a filled box with padding, so it is readable on any background
+pub fn label(
+    img: &mut RgbaImage,
+    x: i64,
+    y: i64,
+    s: &str,
+    scale: u32,
+    color: Rgba<u8>,
+    background: Rgba<u8>,
+) {
+    let (width, height) = text_size(s, scale);
+    fill_rect(
+        img,
+        x - i64::from(scale),
+        y - i64::from(scale),
+        width + 2 * scale,
+        height + 2 * scale,
+        background,
+    );
+    text(img, x, y, s, scale, color);
+}
+
+/// Alpha blend a color onto a pixel. Pixels outside of the image are skipped.
+pub fn blend(img: &mut RgbaImage, x: i64, y: i64, color: Rgba<u8>) {
+    if x < 0 || y < 0 || x >= i64::from(img.width()) || y >= i64::from(img.height()) {
+        return;
+    }
+
+    let pixel = img.get_pixel_mut(x as u32, y as u32);
+    let alpha = u32::from(color[3]);
+    for i in 0..3 {
+        pixel[i] =
+            ((u32::from(color[i]) * alpha + u32::from(pixel[i]) * (255 - alpha)) / 255) as u8;
+    }
+    pixel[3] = (alpha + u32::from(pixel[3]) * (255 - alpha) / 255) as u8;
+}
+
+/// Blend a filled rectangle. Pixels outside of the image are skipped.
+pub fn fill_rect(img: &mut RgbaImage, x: i64, y: i64, width: u32, height: u32, color: Rgba<u8>) {
+    for py in y..y + i64::from(height) {
+        for px in x..x + i64::from(width) {
+            blend(img, px, py, color);
+        }
+    }
+}
+
+/// Blend the outline of a rectangle, drawn inside of it
+pub fn outline_rect(
+    img: &mut RgbaImage,
+    x: i64,
+    y: i64,
+    width: u32,
+    height: u32,
+    thickness: u32,
+    color: Rgba<u8>,
+) {
+    let thickness = thickness.min(width / 2).min(height / 2).max(1);
+    let inner_height = height.saturating_sub(2 * thickness);
+    fill_rect(img, x, y, width, thickness, color);
+    fill_rect(
+        img,
+        x,
+        y + i64::from(height) - i64::from(thickness),
+        width,
+        thickness,
+        color,
+    );
+    fill_rect(
+        img,
+        x,
+        y + i64::from(thickness),
+        thickness,
+        inner_height,
+        color,
+    );
+    fill_rect(
+        img,
+        x + i64::from(width) - i64::from(thickness),
+        y + i64::from(thickness),
+        thickness,
+        inner_height,
+        color,
+    );
+}
+
+/// Blend a line of a given thickness, with round ends
+pub fn line(
+    img: &mut RgbaImage,
+    from: (f32, f32),
+    to: (f32, f32),
+    thickness: f32,
+    color: Rgba<u8>,
+) {
+    let radius = (thickness / 2.0).max(0.5);
+    let (xs, ys) = clip(
+        img,
+        (from.0.min(to.0) - radius, from.1.min(to.1) - radius),
+        (from.0.max(to.0) + radius, from.1.max(to.1) + radius),
+    );
+
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let len_sq = dx * dx + dy * dy;
+    for y in ys {
+        for x in xs.clone() {
+            // Distance from the pixel center to the closest point of the segment
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            let t = if len_sq > 0.0 {
+                (((px - from.0) * dx + (py - from.1) * dy) / len_sq).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let (cx, cy) = (from.0 + t * dx, from.1 + t * dy);
+            if (px - cx).powi(2) + (py - cy).powi(2) <= radius * radius {
+                blend(img, x, y, color);
+            }
+        }
+    }
+}
+
+/// Blend a filled triangle
+pub fn triangle(img: &mut RgbaImage, points: [(f32, f32); 3], color: Rgba<u8>) {
+    let (xs, ys) = clip(
+        img,
+        (
+            points.iter().map(|p| p.0).fold(f32::MAX, f32::min),
+            points.iter().map(|p| p.1).fold(f32::MAX, f32::min),
+        ),
+        (
+            points.iter().map(|p| p.0).fold(f32::MIN, f32::max),
+            points.iter().map(|p| p.1).fold(f32::MIN, f32::max),
+        ),
+    );
+
+    let edge = |a: (f32, f32), b: (f32, f32), p: (f32, f32)| {
+        (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+    };
+    let [a, b, c] = points;
+    for y in ys {
+        for x in xs.clone() {
+            let p = (x as f32 + 0.5, y as f32 + 0.5);
+            let (e0, e1, e2) = (edge(a, b, p), edge(b, c, p), edge(c, a, p));
+            let inside =
+                (e0 >= 0.0 && e1 >= 0.0 && e2 >= 0.0) || (e0 <= 0.0 && e1 <= 0.0 && e2 <= 0.0);
+            if inside {
+                blend(img, x, y, color);
+            }
+        }
+    }
+}
+
+/// Get the pixel columns and rows of a bounding box that are inside of an image
+fn clip(
+    img: &RgbaImage,
+    min: (f32, f32),
+    max: (f32, f32),
+) -> (std::ops::Range<i64>, std::ops::Range<i64>) {
+    let (width, height) = img.dimensions();
+    // Casting saturates, so points far outside of the image can't overflow
+    let range = |min: f32, max: f32, size: u32| {
+        (min.floor() as i64).max(0)..(max.ceil() as i64).saturating_add(1).min(i64::from(size))
+    };
+    (range(min.0, max.0, width), range(min.1, max.1, height))
+}
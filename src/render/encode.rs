@@ -0,0 +1,229 @@
+use crate::{
+    block::Block,
+    format::as3::LevelNum,
+    render::{
+        ImageRenderer,
+        RenderError,
+        RenderOptions,
+    },
+};
+use std::convert::TryInto;
+
+/// The tEXt keyword the lbl source of a level is stored under
+pub const LBL_KEYWORD: &str = "sks-lbl";
+/// The tEXt keyword the level number of a level is stored under
+pub const LEVEL_NUM_KEYWORD: &str = "sks-level-num";
+
+/// The file formats renders can be encoded as
+#[derive(Debug, Clone, PartialEq)]
+pub enum EncodeFormat {
+    /// Lossless, with the level source embedded. A palette quantizes the image to 256 colors.
+    Png { palette: bool },
+    /// Lossy. Transparency is dropped.
+    Jpeg,
+    /// Lossless
+    WebP,
+}
+
+impl ImageRenderer {
+    /// Render a level and encode it.
+    ///
+    /// quality ranges from 1 to 100. For jpeg it is the usual quality setting.
+    /// For png it trades encoding speed for size, and for palette pngs it also picks how carefully colors are chosen.
+    /// WebP is always lossless and ignores it.
+    ///
+    /// Pngs carry the lbl text of the level and its level number in tEXt chunks. Use [`embedded_source`] to read them back.
+    pub fn render_to_bytes(
+        &self,
+        blocks: &[Block],
+        level_num: Option<&LevelNum>,
+        options: &RenderOptions,
+        format: &EncodeFormat,
+        quality: u8,
+    ) -> Result<Vec<u8>, EncodeError> {
+        let img = self.render(blocks, options)?.into_rgba8();
+        let quality = quality.clamp(1, 100);
+        let mut ret = Vec::new();
+
+        match format {
+            EncodeFormat::Png { palette } => {
+                let mut encoder = png::Encoder::new(&mut ret, img.width(), img.height());
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_compression(match quality {
+                    1..=33 => png::Compression::Fast,
+                    34..=66 => png::Compression::Default,
+                    _ => png::Compression::Best,
+                });
+
+                let data = if *palette {
+                    // NeuQuant samples every nth pixel; 1 is the slowest and best
+                    let sample_factor = 1 + (100 - i32::from(quality)) * 29 / 99;
+                    let quant = color_quant::NeuQuant::new(sample_factor, 256, &img);
+                    let map = quant.color_map_rgba();
+                    encoder.set_color(png::ColorType::Indexed);
+                    encoder.set_palette(
+                        map.chunks(4)
+                            .flat_map(|color| color[..3].iter().copied())
+                            .collect(),
+                    );
+                    encoder.set_trns(map.chunks(4).map(|color| color[3]).collect());
+                    img.pixels()
+                        .map(|pixel| quant.index_of(&pixel.0) as u8)
+                        .collect()
+                } else {
+                    encoder.set_color(png::ColorType::RGBA);
+                    img.into_raw()
+                };
+
+                let mut writer = encoder.write_header()?;
+                let lbl = crate::format::lbl::encode(blocks).map_err(EncodeError::Lbl)?;
+                let mut text = vec![("Software", "sks".to_string()), (LBL_KEYWORD, lbl)];
+                text.extend(level_num.map(|n| (LEVEL_NUM_KEYWORD, n.to_string())));
+                for (keyword, value) in text.iter() {
+                    let (kind, data) = text_chunk(keyword, value);
+                    writer.write_chunk(kind, &data)?;
+                }
+                writer.write_image_data(&data)?;
+            }
+            EncodeFormat::Jpeg => {
+                let img = image::DynamicImage::ImageRgba8(img).into_rgb8();
+                image::jpeg::JpegEncoder::new_with_quality(&mut ret, quality).encode(
+                    &img,
+                    img.width(),
+                    img.height(),
+                    image::ColorType::Rgb8,
+                )?;
+            }
+            EncodeFormat::WebP => {
+                image_webp::WebPEncoder::new(&mut ret).encode(
+                    &img,
+                    img.width(),
+                    img.height(),
+                    image_webp::ColorType::Rgba8,
+                )?;
+            }
+        }
+
+        Ok(ret)
+    }
+}
+
+/// Make a tEXt chunk, or an uncompressed iTXt chunk if the text is not latin-1
+fn text_chunk(keyword: &str, text: &str) -> ([u8; 4], Vec<u8>) {
+    let mut data: Vec<u8> = keyword.bytes().chain(std::iter::once(0)).collect();
+    let latin1: Option<Vec<u8>> = text.chars().map(|c| c.try_into().ok()).collect();
+    match latin1 {
+        Some(text) => {
+            data.extend(text);
+            (*b"tEXt", data)
+        }
+        None => {
+            // No compression, then an empty language tag and translated keyword
+            data.extend_from_slice(&[0, 0, 0, 0]);
+            data.extend_from_slice(text.as_bytes());
+            (*b"iTXt", data)
+        }
+    }
+}
+
+/// Iterate over the (type, data) pairs of the chunks of a png. Stops at the first truncated chunk.
+pub(crate) fn png_chunks(png: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    let mut rest = png.strip_prefix(SIGNATURE).unwrap_or(&[]);
+    std::iter::from_fn(move || {
+        if rest.len() < 12 {
+            return None;
+        }
+
+        let len = u32::from_be_bytes(rest[..4].try_into().ok()?) as usize;
+        if rest.len() < 12 + len {
+            return None;
+        }
+
+        let chunk = (&rest[4..8], &rest[8..8 + len]);
+        rest = &rest[12 + len..];
+        Some(chunk)
+    })
+}
+
+/// The source of a level embedded in a png by [`ImageRenderer::render_to_bytes`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddedSource {
+    pub blocks: Vec<Block>,
+    /// Numeric level numbers come back as LevelNum::Num, even if they were strings
+    pub level_num: Option<LevelNum>,
+}
+
+/// Read the level embedded in a png. Returns None if there is none or it is invalid.
+pub fn embedded_source(png: &[u8]) -> Option<EmbeddedSource> {
+    let mut lbl = None;
+    let mut level_num = None;
+    for (kind, data) in png_chunks(png) {
+        let split = match data.iter().position(|b| *b == 0) {
+            Some(split) => split,
+            None => continue,
+        };
+        let (keyword, rest) = (&data[..split], &data[split + 1..]);
+        let text = match kind {
+            // tEXt is latin-1, which maps directly to chars
+            b"tEXt" => rest.iter().map(|b| char::from(*b)).collect(),
+            b"iTXt" if rest.len() >= 4 && rest[0] == 0 => {
+                // Skip the compression method, then the language tag and translated keyword
+                let mut parts = rest[2..].splitn(3, |b| *b == 0);
+                match std::str::from_utf8(parts.nth(2).unwrap_or(&[])) {
+                    Ok(text) => text.to_string(),
+                    Err(_) => continue,
+                }
+            }
+            _ => continue,
+        };
+
+        if keyword == LBL_KEYWORD.as_bytes() {
+            lbl = Some(text);
+        } else if keyword == LEVEL_NUM_KEYWORD.as_bytes() {
+            level_num = Some(match text.parse() {
+                Ok(n) => LevelNum::Num(n),
+                Err(_) => LevelNum::String(text),
+            });
+        }
+    }
+
+    Some(EmbeddedSource {
+        blocks: crate::format::lbl::decode(&lbl?).ok()?,
+        level_num,
+    })
+}
+
+/// Errors that may occur while rendering to bytes
+#[derive(Debug)]
+pub enum EncodeError {
+    Render(RenderError),
+    Lbl(crate::format::lbl::EncodeError),
+    Image(image::ImageError),
+    Png(png::EncodingError),
+    WebP(image_webp::EncodingError),
+}
+
+impl From<RenderError> for EncodeError {
+    fn from(e: RenderError) -> Self {
+        Self::Render(e)
+    }
+}
+
+impl From<image::ImageError> for EncodeError {
+    fn from(e: image::ImageError) -> Self {
+        Self::Image(e)
+    }
+}
+
+impl From<png::EncodingError> for EncodeError {
+    fn from(e: png::EncodingError) -> Self {
+        Self::Png(e)
+    }
+}
+
+impl From<image_webp::EncodingError> for EncodeError {
+    fn from(e: image_webp::EncodingError) -> Self {
+        Self::WebP(e)
+    }
+}
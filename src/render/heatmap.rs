@@ -0,0 +1,277 @@
+use crate::{
+    block::Block,
+    render::{
+        draw,
+        ImageRenderer,
+        Layout,
+        RenderError,
+        RenderOptions,
+    },
+};
+
+/// Colors that values are mapped to. Stops are (position, rgb) pairs, with positions from 0 to 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorRamp {
+    stops: Vec<(f32, [u8; 3])>,
+}
+
+impl ColorRamp {
+    /// Make a ramp from stops. Stops are sorted by position, and positions are clamped to 0..=1.
+    pub fn new(mut stops: Vec<(f32, [u8; 3])>) -> Self {
+        for stop in stops.iter_mut() {
+            stop.0 = stop.0.clamp(0.0, 1.0);
+        }
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { stops }
+    }
+
+    /// Blue through green and yellow to red
+    pub fn heat() -> Self {
+        Self::new(vec![
+            (0.0, [0, 0, 255]),
+            (0.25, [0, 255, 255]),
+            (0.5, [0, 255, 0]),
+            (0.75, [255, 255, 0]),
+            (1.0, [255, 0, 0]),
+        ])
+    }
+
+    /// Black to white
+    pub fn grayscale() -> Self {
+        Self::two_color([0, 0, 0], [255, 255, 255])
+    }
+
+    /// A ramp between two colors
+    pub fn two_color(low: [u8; 3], high: [u8; 3]) -> Self {
+        Self::new(vec![(0.0, low), (1.0, high)])
+    }
+
+    /// Get the color at a position from 0 to 1. Positions outside of that are clamped.
+    pub fn sample(&self, t: f32) -> [u8; 3] {
+        let t = t.clamp(0.0, 1.0);
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return [0, 0, 0],
+        };
+        if t <= first.0 {
+            return first.1;
+        }
+
+        for pair in self.stops.windows(2) {
+            let ((start, low), (end, high)) = (pair[0], pair[1]);
+            if t <= end {
+                let f = if end > start {
+                    (t - start) / (end - start)
+                } else {
+                    1.0
+                };
+                let mix = |a: u8, b: u8| (f32::from(a) + (f32::from(b) - f32::from(a)) * f) as u8;
+                return [
+                    mix(low[0], high[0]),
+                    mix(low[1], high[1]),
+                    mix(low[2], high[2]),
+                ];
+            }
+        }
+
+        last.1
+    }
+}
+
+impl Default for ColorRamp {
+    fn default() -> Self {
+        Self::heat()
+    }
+}
+
+/// Options for rendering a heatmap
+#[derive(Debug)]
+pub struct HeatmapOptions {
+    pub ramp: ColorRamp,
+    /// The opacity of the colors drawn over cells
+    pub opacity: u8,
+    /// The value mapped to the start of the ramp. None uses the smallest value.
+    pub min: Option<f32>,
+    /// The value mapped to the end of the ramp. None uses the largest value.
+    pub max: Option<f32>,
+    /// Leave cells with a value of 0 uncolored
+    pub skip_zero: bool,
+    /// Draw a legend with the ramp and its range in the bottom right corner
+    pub legend: bool,
+}
+
+impl HeatmapOptions {
+    /// Default HeatmapOptions.
+    pub fn new() -> Self {
+        Self {
+            ramp: ColorRamp::heat(),
+            opacity: 160,
+            min: None,
+            max: None,
+            skip_zero: true,
+            legend: true,
+        }
+    }
+
+    /// The colors values are mapped to
+    pub fn ramp(mut self, ramp: ColorRamp) -> Self {
+        self.ramp = ramp;
+        self
+    }
+
+    /// The opacity of the colors drawn over cells
+    pub fn opacity(mut self, opacity: u8) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    /// The values mapped to the start and end of the ramp, instead of the smallest and largest values
+    pub fn range(mut self, min: f32, max: f32) -> Self {
+        self.min = Some(min);
+        self.max = Some(max);
+        self
+    }
+
+    /// Leave cells with a value of 0 uncolored
+    pub fn skip_zero(mut self, skip_zero: bool) -> Self {
+        self.skip_zero = skip_zero;
+        self
+    }
+
+    /// Draw a legend
+    pub fn legend(mut self, legend: bool) -> Self {
+        self.legend = legend;
+        self
+    }
+
+    /// Get the (min, max) range for some values. NaN values are ignored.
+    pub fn value_range(&self, values: &[f32]) -> (f32, f32) {
+        let (low, high) = values
+            .iter()
+            .copied()
+            .filter(|v| !v.is_nan())
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), v| {
+                (low.min(v), high.max(v))
+            });
+        let low = self.min.unwrap_or(if low.is_finite() { low } else { 0.0 });
+        let high = self
+            .max
+            .unwrap_or(if high.is_finite() { high } else { 0.0 });
+        (low, high)
+    }
+}
+
+impl Default for HeatmapOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ImageRenderer {
+    /// Render a level with a color for every cell blended on top.
+    ///
+    /// values has one value per cell, in the same order as blocks. NaN values are left uncolored.
+    pub fn render_heatmap(
+        &self,
+        blocks: &[Block],
+        values: &[f32],
+        options: &RenderOptions,
+        heatmap_options: &HeatmapOptions,
+    ) -> Result<image::DynamicImage, RenderError> {
+        if values.len() != crate::LEVEL_SIZE {
+            return Err(RenderError::InvalidLength(values.len()));
+        }
+
+        let mut img = self.render(blocks, options)?.into_rgba8();
+        let layout = options.layout();
+        let (min, max) = heatmap_options.value_range(values);
+
+        for (i, value) in values.iter().copied().enumerate() {
+            if value.is_nan() || (heatmap_options.skip_zero && value == 0.0) {
+                continue;
+            }
+
+            // A flat range maps everything to the end of the ramp
+            let t = if max > min {
+                (value - min) / (max - min)
+            } else {
+                1.0
+            };
+            let [r, g, b] = heatmap_options.ramp.sample(t);
+            let (x, y) = (
+                (i % crate::LEVEL_WIDTH) as u32,
+                (i / crate::LEVEL_WIDTH) as u32,
+            );
+            let (cell_x, cell_y, w, h) = layout.cell_rect(x, y);
+            draw::fill_rect(
+                &mut img,
+                i64::from(cell_x),
+                i64::from(cell_y),
+                w,
+                h,
+                image::Rgba([r, g, b, heatmap_options.opacity]),
+            );
+        }
+
+        if heatmap_options.legend {
+            draw_legend(&mut img, &layout, &heatmap_options.ramp, min, max);
+        }
+
+        Ok(image::DynamicImage::ImageRgba8(img))
+    }
+}
+
+/// Draw "min [ramp] max" on a dark box in the bottom right corner of the level
+fn draw_legend(img: &mut image::RgbaImage, layout: &Layout, ramp: &ColorRamp, min: f32, max: f32) {
+    let cell_size = layout.cell_width() as u32;
+    let scale = (cell_size / 10).max(1);
+    let (min_label, max_label) = (format_value(min), format_value(max));
+    let (min_width, text_height) = draw::text_size(&min_label, scale);
+    let (max_width, _) = draw::text_size(&max_label, scale);
+
+    let padding = 2 * scale;
+    let bar_width = (cell_size * 4).max(16);
+    let width = min_width + max_width + bar_width + 4 * padding;
+    let height = text_height + 2 * padding;
+    let x = i64::from(layout.x + layout.content_width) - i64::from(width) - i64::from(padding);
+    let y = i64::from(layout.y + layout.content_height) - i64::from(height) - i64::from(padding);
+
+    draw::fill_rect(img, x, y, width, height, image::Rgba([0, 0, 0, 200]));
+    let white = image::Rgba([255, 255, 255, 255]);
+    draw::text(
+        img,
+        x + i64::from(padding),
+        y + i64::from(padding),
+        &min_label,
+        scale,
+        white,
+    );
+
+    let bar_x = x + i64::from(min_width + 2 * padding);
+    for i in 0..bar_width {
+        let [r, g, b] = ramp.sample(i as f32 / (bar_width - 1) as f32);
+        draw::fill_rect(
+            img,
+            bar_x + i64::from(i),
+            y + i64::from(padding),
+            1,
+            text_height,
+            image::Rgba([r, g, b, 255]),
+        );
+    }
+
+    draw::text(
+        img,
+        bar_x + i64::from(bar_width + padding),
+        y + i64::from(padding),
+        &max_label,
+        scale,
+        white,
+    );
+}
+
+/// Format a legend value with at most 2 decimals
+fn format_value(value: f32) -> String {
+    let s = format!("{:.2}", value);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
@@ -0,0 +1,243 @@
+use crate::{
+    block::Block,
+    render::{
+        draw,
+        ImageRenderer,
+        Layout,
+        RenderError,
+        RenderOptions,
+    },
+};
+
+/// A point in cell coordinates. (0, 0) is the top left corner of the level and (1, 1) is the bottom right corner of the first cell.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Point {
+    /// Make a new point
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    /// The center of a cell
+    pub fn cell(x: usize, y: usize) -> Self {
+        Self::new(x as f32 + 0.5, y as f32 + 0.5)
+    }
+
+    /// Get the pixel this point is at in a render
+    pub fn to_pixel(self, layout: &Layout) -> (f32, f32) {
+        (
+            layout.x as f32 + (self.x - layout.cells.x as f32) * layout.cell_width(),
+            layout.y as f32 + (self.y - layout.cells.y as f32) * layout.cell_height(),
+        )
+    }
+}
+
+/// A part of a path
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    /// The end of the segment. It starts where the last one ended.
+    pub to: Point,
+    /// Text drawn at the middle of the segment, like "burrow" or "recall"
+    pub label: Option<String>,
+}
+
+/// A sequence of connected segments, like a route through a level
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    pub start: Point,
+    pub segments: Vec<Segment>,
+    pub color: [u8; 4],
+    /// The width of the line, in cells
+    pub width: f32,
+    /// Draw an arrow head at the end of every segment instead of only the last one
+    pub arrow_every_segment: bool,
+}
+
+impl Path {
+    /// Make a new path with no segments
+    pub fn new(start: Point) -> Self {
+        Self {
+            start,
+            segments: Vec::new(),
+            color: [255, 64, 64, 220],
+            width: 0.15,
+            arrow_every_segment: false,
+        }
+    }
+
+    /// Make a path through the centers of cells, given as (x, y). Returns None if there are no cells.
+    pub fn from_cells<I>(cells: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = (usize, usize)>,
+    {
+        let mut iter = cells.into_iter().map(|(x, y)| Point::cell(x, y));
+        let start = iter.next()?;
+        Some(iter.fold(Self::new(start), Self::line_to))
+    }
+
+    /// Add a segment
+    pub fn line_to(mut self, to: Point) -> Self {
+        self.segments.push(Segment { to, label: None });
+        self
+    }
+
+    /// Add a labeled segment
+    pub fn line_to_labeled<S: Into<String>>(mut self, to: Point, label: S) -> Self {
+        self.segments.push(Segment {
+            to,
+            label: Some(label.into()),
+        });
+        self
+    }
+
+    /// The color of the path, as rgba
+    pub fn color(mut self, color: [u8; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// The width of the line, in cells
+    pub fn width(mut self, width: f32) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Draw an arrow head at the end of every segment instead of only the last one
+    pub fn arrow_every_segment(mut self, arrow_every_segment: bool) -> Self {
+        self.arrow_every_segment = arrow_every_segment;
+        self
+    }
+}
+
+/// Paths drawn on top of a level
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overlay {
+    pub paths: Vec<Path>,
+    /// The size of a label pixel. 0 picks one based on the cell size.
+    pub label_scale: u32,
+    pub label_color: [u8; 4],
+    pub label_background: [u8; 4],
+}
+
+impl Overlay {
+    /// Make an empty overlay
+    pub fn new() -> Self {
+        Self {
+            paths: Vec::new(),
+            label_scale: 0,
+            label_color: [255, 255, 255, 255],
+            label_background: [0, 0, 0, 180],
+        }
+    }
+
+    /// Add a path
+    pub fn path(mut self, path: Path) -> Self {
+        self.paths.push(path);
+        self
+    }
+
+    /// The size of a label pixel. 0 picks one based on the cell size.
+    pub fn label_scale(mut self, label_scale: u32) -> Self {
+        self.label_scale = label_scale;
+        self
+    }
+
+    /// Draw this overlay on a render with the given layout
+    pub fn draw(&self, img: &mut image::RgbaImage, layout: &Layout) {
+        let cell_size = layout.cell_width();
+        let label_scale = if self.label_scale > 0 {
+            self.label_scale
+        } else {
+            ((cell_size / 10.0) as u32).max(1)
+        };
+
+        for path in self.paths.iter() {
+            let color = image::Rgba(path.color);
+            let width = (path.width * cell_size).max(1.0);
+
+            let mut from = path.start.to_pixel(layout);
+            for (i, segment) in path.segments.iter().enumerate() {
+                let to = segment.to.to_pixel(layout);
+                let last = i + 1 == path.segments.len();
+                let arrow = last || path.arrow_every_segment;
+
+                // Stop the line at the base of the arrow head, so the tip stays sharp
+                match arrow_head(from, to, width * 3.0).filter(|_| arrow) {
+                    Some(head) => {
+                        let [_, left, right] = head;
+                        let base = ((left.0 + right.0) / 2.0, (left.1 + right.1) / 2.0);
+                        draw::line(img, from, base, width, color);
+                        draw::triangle(img, head, color);
+                    }
+                    None => draw::line(img, from, to, width, color),
+                }
+
+                from = to;
+            }
+
+            // Labels go on top of every line of the path
+            let mut from = path.start.to_pixel(layout);
+            for segment in path.segments.iter() {
+                let to = segment.to.to_pixel(layout);
+                if let Some(label) = &segment.label {
+                    let (text_width, text_height) = draw::text_size(label, label_scale);
+                    draw::label(
+                        img,
+                        ((from.0 + to.0) / 2.0 - text_width as f32 / 2.0) as i64,
+                        ((from.1 + to.1) / 2.0 - text_height as f32 / 2.0) as i64,
+                        label,
+                        label_scale,
+                        image::Rgba(self.label_color),
+                        image::Rgba(self.label_background),
+                    );
+                }
+                from = to;
+            }
+        }
+    }
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Get the points of an arrow head at the end of a line, as [tip, left, right]. Returns None for empty lines.
+fn arrow_head(from: (f32, f32), to: (f32, f32), size: f32) -> Option<[(f32, f32); 3]> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let len = (dx * dx + dy * dy).sqrt();
+    if len == 0.0 {
+        return None;
+    }
+
+    // Keep the head from being longer than the segment
+    let size = size.min(len);
+    let (ux, uy) = (dx / len, dy / len);
+    let base = (to.0 - ux * size, to.1 - uy * size);
+    let half = size / 2.0;
+
+    Some([
+        to,
+        (base.0 - uy * half, base.1 + ux * half),
+        (base.0 + uy * half, base.1 - ux * half),
+    ])
+}
+
+impl ImageRenderer {
+    /// Render a level with an overlay on top. blocks must be the right size.
+    pub fn render_overlay(
+        &self,
+        blocks: &[Block],
+        options: &RenderOptions,
+        overlay: &Overlay,
+    ) -> Result<image::DynamicImage, RenderError> {
+        let mut img = self.render(blocks, options)?.into_rgba8();
+        overlay.draw(&mut img, &options.layout());
+        Ok(image::DynamicImage::ImageRgba8(img))
+    }
+}
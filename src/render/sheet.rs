@@ -0,0 +1,309 @@
+use crate::{
+    block::Block,
+    format::as3::LevelNum,
+    render::{
+        draw,
+        ImageRenderer,
+        RenderError,
+        RenderOptions,
+    },
+};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+};
+
+/// The most pixels a contact sheet may have
+const MAX_SHEET_PIXELS: u64 = 1 << 28;
+
+/// A level on a contact sheet
+#[derive(Debug, Clone)]
+pub struct SheetEntry<'a> {
+    pub blocks: &'a [Block],
+    /// Used for the caption and the world map layout
+    pub level_num: Option<LevelNum>,
+}
+
+impl<'a> SheetEntry<'a> {
+    /// Make a new entry without a level number
+    pub fn new(blocks: &'a [Block]) -> Self {
+        Self {
+            blocks,
+            level_num: None,
+        }
+    }
+
+    /// The level number of this entry
+    pub fn level_num(mut self, level_num: LevelNum) -> Self {
+        self.level_num = Some(level_num);
+        self
+    }
+}
+
+/// How levels are arranged on a contact sheet
+#[derive(Debug, Clone, PartialEq)]
+pub enum SheetLayout {
+    /// Levels in order, starting a new row after every columns levels
+    Grid { columns: usize },
+    /// Levels by world and level index.
+    ///
+    /// Level numbers like "2-4" are placed in the row of world 2, at column 4.
+    /// A numeric level number n is placed in row n / levels_per_world, at column n % levels_per_world.
+    /// Levels without a position, or with a position that is already taken, follow in new rows.
+    WorldMap { levels_per_world: usize },
+}
+
+/// Options for rendering a contact sheet
+#[derive(Debug)]
+pub struct SheetOptions {
+    /// The options used for every thumbnail
+    pub thumbnail: RenderOptions,
+    pub layout: SheetLayout,
+    /// The space between thumbnails and around the edges, in pixels
+    pub spacing: u32,
+    /// The width of the border around every thumbnail, in pixels. 0 disables borders.
+    pub border_width: u32,
+    pub border_color: [u8; 4],
+    pub background_color: [u8; 4],
+    /// The size of a caption pixel. 0 disables captions.
+    pub caption_scale: u32,
+    pub caption_color: [u8; 4],
+}
+
+impl SheetOptions {
+    /// Default SheetOptions.
+    pub fn new() -> Self {
+        Self {
+            thumbnail: RenderOptions::new().cell_size(8),
+            layout: SheetLayout::Grid { columns: 8 },
+            spacing: 8,
+            border_width: 0,
+            border_color: [255, 255, 255, 255],
+            background_color: [32, 32, 32, 255],
+            caption_scale: 2,
+            caption_color: [255, 255, 255, 255],
+        }
+    }
+
+    /// The options used for every thumbnail
+    pub fn thumbnail(mut self, thumbnail: RenderOptions) -> Self {
+        self.thumbnail = thumbnail;
+        self
+    }
+
+    /// How levels are arranged
+    pub fn layout(mut self, layout: SheetLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// The space between thumbnails and around the edges, in pixels
+    pub fn spacing(mut self, spacing: u32) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    /// Draw a border around every thumbnail
+    pub fn border(mut self, width: u32, color: [u8; 4]) -> Self {
+        self.border_width = width;
+        self.border_color = color;
+        self
+    }
+
+    /// The color behind the thumbnails
+    pub fn background_color(mut self, color: [u8; 4]) -> Self {
+        self.background_color = color;
+        self
+    }
+
+    /// Caption thumbnails with their level numbers. A scale of 0 disables captions.
+    pub fn captions(mut self, scale: u32, color: [u8; 4]) -> Self {
+        self.caption_scale = scale;
+        self.caption_color = color;
+        self
+    }
+}
+
+impl Default for SheetOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ImageRenderer {
+    /// Render a contact sheet of many levels. Thumbnails are rendered in parallel and share this renderer's cache.
+    pub fn render_sheet(
+        &self,
+        entries: &[SheetEntry],
+        options: &SheetOptions,
+    ) -> Result<image::DynamicImage, RenderError> {
+        let positions = sheet_positions(entries, &options.layout);
+        let layout = options.thumbnail.layout();
+        let caption_height = if options.caption_scale > 0 {
+            (draw::GLYPH_HEIGHT + 2) * options.caption_scale
+        } else {
+            0
+        };
+        let border = options.border_width;
+        let slot_width = layout.width + 2 * border;
+        let slot_height = layout.height + 2 * border + caption_height;
+        let spacing = options.spacing;
+
+        let too_large = || RenderError::SheetTooLarge {
+            columns: positions.iter().map(|(_, col)| *col).max().unwrap_or(0),
+            rows: positions.iter().map(|(row, _)| *row).max().unwrap_or(0),
+        };
+        // Level numbers come from the caller, so they may ask for any number of rows and columns
+        let extent = |count: Option<usize>, slot: u32| -> Option<u32> {
+            let count = u32::try_from(count?).ok()?;
+            let size = count
+                .checked_mul(slot)?
+                .checked_add(count.checked_add(1)?.checked_mul(spacing)?)?;
+            Some(size)
+        };
+        let columns = positions
+            .iter()
+            .map(|(_, col)| col.checked_add(1))
+            .max()
+            .unwrap_or(Some(0));
+        let rows = positions
+            .iter()
+            .map(|(row, _)| row.checked_add(1))
+            .max()
+            .unwrap_or(Some(0));
+        let (width, height) = match (extent(columns, slot_width), extent(rows, slot_height)) {
+            (Some(width), Some(height))
+                if u64::from(width) * u64::from(height) <= MAX_SHEET_PIXELS =>
+            {
+                (width, height)
+            }
+            _ => return Err(too_large()),
+        };
+
+        let levels: Vec<&[Block]> = entries.iter().map(|entry| entry.blocks).collect();
+        let thumbnails = self
+            .render_all(&levels, &options.thumbnail)
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut sheet =
+            image::RgbaImage::from_pixel(width, height, image::Rgba(options.background_color));
+
+        for ((entry, thumbnail), (row, col)) in entries.iter().zip(thumbnails).zip(positions) {
+            let x = spacing + col as u32 * (slot_width + spacing);
+            let y = spacing + row as u32 * (slot_height + spacing);
+
+            if border > 0 {
+                draw::outline_rect(
+                    &mut sheet,
+                    i64::from(x),
+                    i64::from(y),
+                    slot_width,
+                    layout.height + 2 * border,
+                    border,
+                    image::Rgba(options.border_color),
+                );
+            }
+            image::imageops::overlay(&mut sheet, &thumbnail, x + border, y + border);
+
+            if let (Some(level_num), true) = (&entry.level_num, caption_height > 0) {
+                let scale = options.caption_scale;
+                let max_chars = ((slot_width / scale + 1) / draw::GLYPH_ADVANCE) as usize;
+                let caption: String = level_num.to_string().chars().take(max_chars).collect();
+                let (text_width, _) = draw::text_size(&caption, scale);
+                draw::text(
+                    &mut sheet,
+                    i64::from(x + (slot_width - text_width) / 2),
+                    i64::from(y + layout.height + 2 * border + scale),
+                    &caption,
+                    scale,
+                    image::Rgba(options.caption_color),
+                );
+            }
+        }
+
+        Ok(image::DynamicImage::ImageRgba8(sheet))
+    }
+}
+
+/// Get the (row, column) of every entry
+fn sheet_positions(entries: &[SheetEntry], layout: &SheetLayout) -> Vec<(usize, usize)> {
+    match layout {
+        SheetLayout::Grid { columns } => {
+            let columns = (*columns).max(1);
+            (0..entries.len())
+                .map(|i| (i / columns, i % columns))
+                .collect()
+        }
+        SheetLayout::WorldMap { levels_per_world } => {
+            let levels_per_world = (*levels_per_world).max(1);
+            let wanted: Vec<_> = entries
+                .iter()
+                .map(|entry| world_position(entry.level_num.as_ref()?, levels_per_world))
+                .collect();
+
+            // Remove empty rows and columns before the first level
+            let min_row = wanted
+                .iter()
+                .flatten()
+                .map(|(row, _)| *row)
+                .min()
+                .unwrap_or(0);
+            let min_col = wanted
+                .iter()
+                .flatten()
+                .map(|(_, col)| *col)
+                .min()
+                .unwrap_or(0);
+
+            let mut taken = HashMap::new();
+            for (i, pos) in wanted.iter().enumerate() {
+                if let Some((row, col)) = pos {
+                    taken.entry((row - min_row, col - min_col)).or_insert(i);
+                }
+            }
+
+            // Huge level numbers saturate, and are rejected with the size of the sheet
+            let columns = taken
+                .keys()
+                .map(|(_, col)| col.saturating_add(1))
+                .max()
+                .unwrap_or(levels_per_world);
+            let mut next = taken
+                .keys()
+                .map(|(row, _)| row.saturating_add(1))
+                .max()
+                .unwrap_or(0)
+                .saturating_mul(columns);
+
+            let mut positions = vec![(0, 0); entries.len()];
+            for (pos, i) in taken.iter() {
+                positions[*i] = *pos;
+            }
+            for (i, position) in positions.iter_mut().enumerate() {
+                let placed = wanted[i].is_some_and(|(row, col)| {
+                    taken.get(&(row - min_row, col - min_col)) == Some(&i)
+                });
+                if !placed {
+                    *position = (next / columns, next % columns);
+                    next = next.saturating_add(1);
+                }
+            }
+
+            positions
+        }
+    }
+}
+
+/// Get the (world, level) position of a level number
+fn world_position(level_num: &LevelNum, levels_per_world: usize) -> Option<(usize, usize)> {
+    match level_num {
+        LevelNum::Num(n) => Some((n / levels_per_world, n % levels_per_world)),
+        LevelNum::String(s) => {
+            let mut iter = s.splitn(2, '-');
+            let world = iter.next()?.trim().parse().ok()?;
+            let level = iter.next()?.trim().parse().ok()?;
+            Some((world, level))
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use crate::{
+    block::{
+        BackgroundType,
+        Block,
+        Direction,
+    },
+    render::{
+        ImageRenderer,
+        RenderError,
+        RenderOptions,
+    },
+};
+use image::GenericImageView;
+use std::fmt::Write;
+
+/// The reset escape sequence
+const RESET: &str = "\x1b[0m";
+
+/// A block renderer that produces text for terminals
+pub struct TextRenderer {
+    images: ImageRenderer,
+}
+
+impl TextRenderer {
+    /// Create a new renderer
+    pub fn new() -> Self {
+        Self {
+            images: ImageRenderer::new(),
+        }
+    }
+
+    /// Render a level as a string. blocks must be the right size.
+    ///
+    /// Lines are separated with '\n', and every colored line ends with a style reset.
+    pub fn render(
+        &self,
+        blocks: &[Block],
+        options: &TextRenderOptions,
+    ) -> Result<String, RenderError> {
+        let len = blocks.len();
+        if len != crate::LEVEL_SIZE {
+            return Err(RenderError::InvalidLength(len));
+        }
+
+        match (&options.mode, &options.color) {
+            // Half blocks need a color for each half, so fall back to glyphs without color.
+            (TextMode::HalfBlock, ColorMode::None) => {
+                Ok(render_glyphs(blocks, true, &options.color))
+            }
+            (TextMode::HalfBlock, color) => self.render_half_blocks(blocks, options, color),
+            (TextMode::Unicode, color) => Ok(render_glyphs(blocks, false, color)),
+            (TextMode::Ascii, color) => Ok(render_glyphs(blocks, true, color)),
+        }
+    }
+
+    /// Render the actual textures, using the upper half block to fit 2 pixels in every character.
+    fn render_half_blocks(
+        &self,
+        blocks: &[Block],
+        options: &TextRenderOptions,
+        color: &ColorMode,
+    ) -> Result<String, RenderError> {
+        let cell_size = options.cell_size.max(1) as usize;
+        let render_options = RenderOptions::new()
+            .width(crate::LEVEL_WIDTH * cell_size)
+            .height(crate::LEVEL_HEIGHT * cell_size);
+        let img = self.images.render(blocks, &render_options)?;
+        let (width, height) = img.dimensions();
+
+        let mut ret = String::new();
+        for y in (0..height).step_by(2) {
+            let (mut last_fg, mut last_bg) = (None, None);
+            for x in 0..width {
+                let top = img.get_pixel(x, y);
+                let bottom = if y + 1 < height {
+                    img.get_pixel(x, y + 1)
+                } else {
+                    top
+                };
+
+                let (fg, bg) = ([top[0], top[1], top[2]], [bottom[0], bottom[1], bottom[2]]);
+                if last_fg != Some(fg) {
+                    push_fg(&mut ret, color, fg);
+                    last_fg = Some(fg);
+                }
+                if last_bg != Some(bg) {
+                    push_bg(&mut ret, color, bg);
+                    last_bg = Some(bg);
+                }
+                ret.push('▀');
+            }
+            ret += RESET;
+            ret.push('\n');
+        }
+
+        Ok(ret)
+    }
+}
+
+impl Default for TextRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Render each block as a single glyph
+fn render_glyphs(blocks: &[Block], ascii: bool, color: &ColorMode) -> String {
+    // Conservative estimate: a glyph + some escapes per block and a '\n' per row
+    let mut ret = String::with_capacity(crate::LEVEL_SIZE * 2);
+    for row in blocks.chunks(crate::LEVEL_WIDTH) {
+        let mut last_color = None;
+        for block in row {
+            let glyph = Glyph::from_block(block);
+            if last_color != Some(glyph.color) {
+                push_fg(&mut ret, color, glyph.color);
+                last_color = Some(glyph.color);
+            }
+
+            ret.push(if ascii { glyph.ascii } else { glyph.unicode });
+        }
+
+        if *color != ColorMode::None {
+            ret += RESET;
+        }
+        ret.push('\n');
+    }
+
+    ret
+}
+
+/// Append an escape to set the foreground color
+fn push_fg(s: &mut String, color: &ColorMode, rgb: [u8; 3]) {
+    match color {
+        ColorMode::None => {}
+        ColorMode::Ansi256 => {
+            let _ = write!(s, "\x1b[38;5;{}m", ansi256(rgb));
+        }
+        ColorMode::TrueColor => {
+            let _ = write!(s, "\x1b[38;2;{};{};{}m", rgb[0], rgb[1], rgb[2]);
+        }
+    }
+}
+
+/// Append an escape to set the background color
+fn push_bg(s: &mut String, color: &ColorMode, rgb: [u8; 3]) {
+    match color {
+        ColorMode::None => {}
+        ColorMode::Ansi256 => {
+            let _ = write!(s, "\x1b[48;5;{}m", ansi256(rgb));
+        }
+        ColorMode::TrueColor => {
+            let _ = write!(s, "\x1b[48;2;{};{};{}m", rgb[0], rgb[1], rgb[2]);
+        }
+    }
+}
+
+/// Map a color to the closest entry of the 6x6x6 color cube of the 256 color palette
+fn ansi256(rgb: [u8; 3]) -> u8 {
+    let [r, g, b] = rgb;
+    let scale = |v: u8| ((u16::from(v) * 5 + 127) / 255) as u8;
+    16 + 36 * scale(r) + 6 * scale(g) + scale(b)
+}
+
+/// How a block looks in a terminal
+struct Glyph {
+    ascii: char,
+    unicode: char,
+    color: [u8; 3],
+}
+
+impl Glyph {
+    const fn new(ascii: char, unicode: char, color: [u8; 3]) -> Self {
+        Self {
+            ascii,
+            unicode,
+            color,
+        }
+    }
+
+    fn from_block(block: &Block) -> Self {
+        match block {
+            Block::Background { background_type } => {
+                let color = match background_type {
+                    BackgroundType::Cobble => [96, 96, 96],
+                    BackgroundType::Waterfall => [64, 96, 192],
+                    BackgroundType::Skullfall => [192, 192, 160],
+                    BackgroundType::Concrete => [160, 160, 160],
+                    BackgroundType::Reserved1
+                    | BackgroundType::Reserved2
+                    | BackgroundType::Reserved3 => [128, 128, 128],
+                };
+                Self::new(' ', ' ', color)
+            }
+            Block::Block => Self::new('#', '█', [200, 200, 200]),
+            Block::Dark => Self::new('%', '▓', [64, 64, 64]),
+            Block::Empty => Self::new(' ', ' ', [255, 255, 255]),
+            Block::Exit => Self::new('E', '∩', [64, 220, 64]),
+            Block::Key => Self::new('k', '¤', [255, 215, 0]),
+            Block::Lock => Self::new('L', '▣', [205, 133, 63]),
+            Block::Note { .. } => Self::new('?', '¶', [240, 230, 140]),
+            Block::Scaffold => Self::new('=', '═', [160, 110, 60]),
+            Block::SecretExit => Self::new('e', '∩', [64, 160, 220]),
+            Block::Switch => Self::new('s', '┴', [220, 64, 64]),
+            Block::SwitchCeiling => Self::new('S', '┬', [220, 64, 64]),
+            Block::OneWayWall { direction } => {
+                let (ascii, unicode) = match direction {
+                    Direction::Up => ('^', '▲'),
+                    Direction::Down => ('v', '▼'),
+                    Direction::Left => ('<', '◀'),
+                    Direction::Right => ('>', '▶'),
+                };
+                Self::new(ascii, unicode, [135, 206, 250])
+            }
+            Block::PipeIn => Self::new('i', '○', [50, 205, 50]),
+            Block::PipeOut => Self::new('o', '●', [50, 205, 50]),
+            Block::PipePhase => Self::new(':', '░', [50, 205, 50]),
+            Block::PipeSolid => Self::new('+', '▒', [50, 205, 50]),
+            Block::Player => Self::new('@', '☺', [255, 255, 255]),
+            Block::PowerUpBurrow => Self::new('b', '↓', [186, 85, 211]),
+            Block::PowerUpRecall => Self::new('r', '↺', [186, 85, 211]),
+            Block::ToggleBlock { solid: true } => Self::new('T', '■', [220, 64, 64]),
+            Block::ToggleBlock { solid: false } => Self::new('t', '□', [220, 64, 64]),
+            Block::Torch => Self::new('!', '†', [255, 140, 0]),
+            Block::Wire => Self::new('-', '┼', [178, 34, 34]),
+        }
+    }
+}
+
+/// The glyphs to draw with
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextMode {
+    /// One ascii character per block
+    Ascii,
+    /// One unicode character per block
+    Unicode,
+    /// Pixel art of the actual textures, drawn with half blocks. Falls back to ascii if there is no color.
+    HalfBlock,
+}
+
+/// The colors a terminal supports
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorMode {
+    None,
+    Ansi256,
+    TrueColor,
+}
+
+/// Options for rendering text
+#[derive(Debug)]
+pub struct TextRenderOptions {
+    pub mode: TextMode,
+    pub color: ColorMode,
+    pub cell_size: u32,
+}
+
+impl TextRenderOptions {
+    /// Default TextRenderOptions. This is plain ascii, which works everywhere.
+    pub fn new() -> Self {
+        Self {
+            mode: TextMode::Ascii,
+            color: ColorMode::None,
+            cell_size: 2,
+        }
+    }
+
+    /// Guess the best options for the current terminal from the environment.
+    ///
+    /// Respects NO_COLOR, COLORTERM, TERM and the locale variables.
+    pub fn detect() -> Self {
+        let var = |name| std::env::var(name).unwrap_or_default();
+
+        let term = var("TERM");
+        let color_term = var("COLORTERM");
+        let color = if std::env::var_os("NO_COLOR").is_some() || term == "dumb" {
+            ColorMode::None
+        } else if color_term == "truecolor" || color_term == "24bit" {
+            ColorMode::TrueColor
+        } else if term.contains("256color") {
+            ColorMode::Ansi256
+        } else {
+            ColorMode::None
+        };
+
+        let utf8 = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .map(|name| var(name))
+            .find(|v| !v.is_empty())
+            .is_some_and(|v| {
+                let v = v.to_ascii_lowercase();
+                v.contains("utf-8") || v.contains("utf8")
+            });
+        let mode = match (utf8, &color) {
+            (true, ColorMode::None) => TextMode::Unicode,
+            (true, _) => TextMode::HalfBlock,
+            (false, _) => TextMode::Ascii,
+        };
+
+        Self::new().mode(mode).color(color)
+    }
+
+    /// The glyphs to use
+    pub fn mode(mut self, mode: TextMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// The colors to use
+    pub fn color(mut self, color: ColorMode) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// The width of a block in characters for half block rendering. A block is half as many characters tall.
+    pub fn cell_size(mut self, cell_size: u32) -> Self {
+        self.cell_size = cell_size;
+        self
+    }
+}
+
+impl Default for TextRenderOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,495 @@
+use crate::{
+    block::Block,
+    render::ImageRequest,
+};
+use image::GenericImageView;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    io::{
+        Read,
+        Seek,
+    },
+    path::{
+        Component,
+        Path,
+    },
+    sync::{
+        Arc,
+        Mutex,
+        PoisonError,
+    },
+};
+
+/// The name of the manifest file in a texture pack
+pub const MANIFEST_NAME: &str = "manifest.txt";
+
+/// The largest file read from a zip texture pack, in bytes. Zip headers can claim any size, so they aren't trusted.
+pub const MAX_ZIP_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// The names of all textures a pack may provide. These are lbl codes, except for notes, which use "NO".
+pub const TEXTURE_NAMES: &[&str] = &[
+    "A0", "B0", "BK", "CI", "CO", "CP", "CS", "D0", "D1", "E0", "E1", "IK", "M0", "M1", "M2", "M3",
+    "M4", "M5", "M6", "NO", "OD", "OL", "OR", "OU", "P0", "P1", "S0", "S1", "T0", "T1", "WR", "X0",
+];
+
+/// Get the name of the texture used for a block
+pub fn texture_name(block: &Block) -> Cow<'static, str> {
+    match block {
+        Block::Note { .. } => "NO".into(),
+        block => block.as_lbl(),
+    }
+}
+
+/// Get the embedded default texture for a texture name, if there is one
+pub fn embedded_texture(name: &str) -> Option<&'static [u8]> {
+    use super::*;
+
+    Some(match name {
+        "M0" => M0,
+        "B0" => B0,
+        "E0" => E0,
+        "IK" => IK,
+        "BK" => BK,
+        "NO" => NO,
+        "OD" => OD,
+        "OU" => OU,
+        "OL" => OL,
+        "OR" => OR,
+        "CI" => CI,
+        "CO" => CO,
+        "CP" => CP,
+        "CS" => CS,
+        "X0" => X0,
+        "P0" => P0,
+        "P1" => P1,
+        "D0" => D0,
+        "S0" => S0,
+        "T0" => T0,
+        "T1" => T1,
+        "D1" => D1,
+        _ => return None,
+    })
+}
+
+/// A rectangle in an image, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    /// Make a new rect
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Parse a rect of the form `x, y, width, height`
+    pub fn parse(data: &str) -> Option<Self> {
+        let mut iter = data.split(',').map(|n| n.trim().parse::<u32>());
+        let rect = Self::new(
+            iter.next()?.ok()?,
+            iter.next()?.ok()?,
+            iter.next()?.ok()?,
+            iter.next()?.ok()?,
+        );
+
+        if iter.next().is_some() {
+            return None;
+        }
+
+        Some(rect)
+    }
+}
+
+/// Where the sprites of a texture atlas are
+#[derive(Debug, Clone, Default)]
+pub struct AtlasDefinition {
+    sprites: HashMap<String, Rect>,
+}
+
+impl AtlasDefinition {
+    /// Make an empty definition
+    pub fn new() -> Self {
+        Self {
+            sprites: HashMap::new(),
+        }
+    }
+
+    /// Parse a definition. Each line maps a texture name to a rect, like `B0 = 0, 0, 16, 16`.
+    /// Empty lines and lines starting with '#' are ignored.
+    pub fn parse(data: &str) -> Result<Self, TextureError> {
+        let mut def = Self::new();
+        for (line, name, rect) in parse_manifest(data)? {
+            let rect = Rect::parse(rect).ok_or(TextureError::InvalidManifestLine(line))?;
+            def.insert_name(name, rect)?;
+        }
+
+        Ok(def)
+    }
+
+    /// Set the sprite for a block. This covers every direction and background type, as they are different blocks.
+    pub fn insert(&mut self, block: &Block, rect: Rect) {
+        self.sprites.insert(texture_name(block).into_owned(), rect);
+    }
+
+    /// Set the sprite for a texture name. name must be in TEXTURE_NAMES.
+    pub fn insert_name<S: Into<String>>(
+        &mut self,
+        name: S,
+        rect: Rect,
+    ) -> Result<(), TextureError> {
+        let name = name.into();
+        if !TEXTURE_NAMES.contains(&name.as_str()) {
+            return Err(TextureError::UnknownTextureName(name));
+        }
+
+        self.sprites.insert(name, rect);
+        Ok(())
+    }
+
+    /// Get the sprite for a block
+    pub fn get(&self, block: &Block) -> Option<Rect> {
+        self.sprites.get(texture_name(block).as_ref()).copied()
+    }
+}
+
+/// A texture in a pack
+#[derive(Debug, Clone)]
+enum Texture {
+    Image(Arc<image::DynamicImage>),
+    /// A sprite in one of the pack's atlases. It is only cut out when it is rendered.
+    Sprite {
+        atlas: usize,
+        rect: Rect,
+    },
+}
+
+/// A set of block textures. Textures missing from the pack fall back to the embedded defaults.
+///
+/// A pack on disk is a directory or zip file containing pngs and a manifest named "manifest.txt".
+/// Each line of the manifest maps a texture name to a path relative to the pack root, like `B0 = block.png`.
+/// Empty lines and lines starting with '#' are ignored.
+/// Without a manifest, files named after the textures, like "B0.png", are used.
+///
+/// A manifest may also use a texture atlas by adding a line like `atlas = sheet.png`.
+/// Later lines may then map a texture name to a rect in that atlas instead of a file, like `B0 = 0, 0, 16, 16`.
+///
+/// Embedded textures are decoded once and shared between clones of a pack.
+#[derive(Debug, Clone, Default)]
+pub struct TexturePack {
+    textures: HashMap<String, Texture>,
+    atlases: Vec<image::DynamicImage>,
+    embedded: Arc<Mutex<HashMap<String, Arc<image::DynamicImage>>>>,
+}
+
+impl TexturePack {
+    /// A pack with only the embedded default textures
+    pub fn new() -> Self {
+        Self {
+            textures: HashMap::new(),
+            atlases: Vec::new(),
+            embedded: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// A pack using sprites from a texture atlas
+    pub fn from_atlas(
+        atlas: image::DynamicImage,
+        def: &AtlasDefinition,
+    ) -> Result<Self, TextureError> {
+        let mut pack = Self::new();
+        pack.add_atlas(atlas, def)?;
+        Ok(pack)
+    }
+
+    /// Load a pack from a directory
+    pub fn from_dir<P: AsRef<Path>>(path: P) -> Result<Self, TextureError> {
+        let path = path.as_ref();
+        let manifest_path = path.join(MANIFEST_NAME);
+        let manifest = if manifest_path.exists() {
+            Some(std::fs::read_to_string(manifest_path)?)
+        } else {
+            None
+        };
+
+        Self::load(manifest.as_deref(), |file| {
+            let file_path = path.join(file);
+            if file_path.exists() {
+                Ok(Some(std::fs::read(file_path)?))
+            } else {
+                Ok(None)
+            }
+        })
+    }
+
+    /// Load a pack from a zip file on disk
+    pub fn from_zip_file<P: AsRef<Path>>(path: P) -> Result<Self, TextureError> {
+        Self::from_zip(std::fs::File::open(path)?)
+    }
+
+    /// Load a pack from a zip archive
+    pub fn from_zip<R: Read + Seek>(reader: R) -> Result<Self, TextureError> {
+        let mut archive = zip::ZipArchive::new(reader)?;
+        let mut read_file = |file: &str| -> Result<Option<Vec<u8>>, TextureError> {
+            let entry = match archive.by_name(file) {
+                Ok(entry) => entry,
+                Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+            let mut data = Vec::new();
+            entry.take(MAX_ZIP_FILE_SIZE + 1).read_to_end(&mut data)?;
+            if data.len() as u64 > MAX_ZIP_FILE_SIZE {
+                return Err(TextureError::FileTooLarge(String::from(file)));
+            }
+            Ok(Some(data))
+        };
+
+        let manifest = read_file(MANIFEST_NAME)?
+            .map(String::from_utf8)
+            .transpose()
+            .map_err(|_| TextureError::InvalidManifestEncoding)?;
+
+        Self::load(manifest.as_deref(), read_file)
+    }
+
+    /// Load a pack, using read_file to get the contents of files in the pack
+    fn load<F>(manifest: Option<&str>, mut read_file: F) -> Result<Self, TextureError>
+    where
+        F: FnMut(&str) -> Result<Option<Vec<u8>>, TextureError>,
+    {
+        let mut pack = Self::new();
+        match manifest {
+            Some(manifest) => {
+                // Paths may not leave the pack
+                let mut read_file = |file: &str| {
+                    let is_inside = Path::new(file)
+                        .components()
+                        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+                    if !is_inside {
+                        return Err(TextureError::InvalidPath(String::from(file)));
+                    }
+                    read_file(file)
+                };
+
+                let mut atlas = None;
+                for (line, name, value) in parse_manifest(manifest)? {
+                    if name == "atlas" {
+                        let data = read_file(value)?
+                            .ok_or_else(|| TextureError::MissingFile(String::from(value)))?;
+                        pack.atlases.push(image::load_from_memory(&data)?);
+                        atlas = Some(pack.atlases.len() - 1);
+                        continue;
+                    }
+
+                    if !TEXTURE_NAMES.contains(&name) {
+                        return Err(TextureError::UnknownTextureName(String::from(name)));
+                    }
+
+                    match (atlas, Rect::parse(value)) {
+                        (Some(atlas), Some(rect)) => {
+                            pack.insert_sprite(name, atlas, rect)?;
+                        }
+                        (None, Some(_)) => {
+                            return Err(TextureError::InvalidManifestLine(line));
+                        }
+                        (_, None) => {
+                            let data = read_file(value)?
+                                .ok_or_else(|| TextureError::MissingFile(String::from(value)))?;
+                            pack.insert(name, image::load_from_memory(&data)?)?;
+                        }
+                    }
+                }
+            }
+            None => {
+                for name in TEXTURE_NAMES {
+                    if let Some(data) = read_file(&format!("{}.png", name))? {
+                        pack.insert(*name, image::load_from_memory(&data)?)?;
+                    }
+                }
+            }
+        }
+
+        Ok(pack)
+    }
+
+    /// Add a texture, replacing the old one if it exists. name must be in TEXTURE_NAMES.
+    pub fn insert<S: Into<String>>(
+        &mut self,
+        name: S,
+        image: image::DynamicImage,
+    ) -> Result<(), TextureError> {
+        let name = name.into();
+        if !TEXTURE_NAMES.contains(&name.as_str()) {
+            return Err(TextureError::UnknownTextureName(name));
+        }
+
+        self.textures.insert(name, Texture::Image(Arc::new(image)));
+        Ok(())
+    }
+
+    /// Add a texture atlas. Sprites in the definition replace existing textures.
+    /// If any sprite is invalid, the pack is left unchanged.
+    pub fn add_atlas(
+        &mut self,
+        atlas: image::DynamicImage,
+        def: &AtlasDefinition,
+    ) -> Result<(), TextureError> {
+        let mut sprites: Vec<_> = def.sprites.iter().collect();
+        sprites.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        for (name, rect) in sprites.iter() {
+            check_sprite(&atlas, name, **rect)?;
+        }
+
+        self.atlases.push(atlas);
+        let index = self.atlases.len() - 1;
+        for (name, rect) in sprites {
+            self.textures.insert(
+                name.clone(),
+                Texture::Sprite {
+                    atlas: index,
+                    rect: *rect,
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Add a sprite from an atlas of this pack, validating it
+    fn insert_sprite(&mut self, name: &str, atlas: usize, rect: Rect) -> Result<(), TextureError> {
+        check_sprite(&self.atlases[atlas], name, rect)?;
+        self.textures
+            .insert(String::from(name), Texture::Sprite { atlas, rect });
+        Ok(())
+    }
+
+    /// Get the unscaled texture for a block, if it has one
+    pub fn get(&self, block: &Block) -> Option<Arc<image::DynamicImage>> {
+        let name = texture_name(block);
+        match self.textures.get(name.as_ref()) {
+            Some(Texture::Image(img)) => return Some(img.clone()),
+            Some(Texture::Sprite { atlas, rect }) => {
+                return Some(Arc::new(self.atlases[*atlas].crop_imm(
+                    rect.x,
+                    rect.y,
+                    rect.width,
+                    rect.height,
+                )));
+            }
+            None => {}
+        }
+
+        let data = embedded_texture(&name)?;
+        let embedded = || self.embedded.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(img) = embedded().get(name.as_ref()) {
+            return Some(img.clone());
+        }
+
+        // Decode without holding the lock, so other threads can use textures that are already decoded.
+        // If another thread decoded this one first, its copy is kept.
+        let img = Arc::new(image::load_from_memory(data).expect("Valid Embedded image"));
+        Some(embedded().entry(name.into_owned()).or_insert(img).clone())
+    }
+
+    /// Returns true if this pack provides a custom texture for a block
+    pub fn contains(&self, block: &Block) -> bool {
+        self.textures.contains_key(texture_name(block).as_ref())
+    }
+
+    /// Generates a new block image. returns None if there is no texture for the block. TODO: Consider Error Texture
+    ///
+    /// Block textures keep their aspect ratio, so the image may be smaller than requested on one side.
+    /// Backgrounds cover the whole level, so they are stretched to the requested size.
+    pub fn generate_block_image(&self, r: &ImageRequest) -> Option<image::DynamicImage> {
+        if r.block.is_empty() {
+            return None;
+        }
+
+        let texture = self.get(&r.block)?;
+        let filter = r.filter.as_filter_type();
+        if r.block.is_background() {
+            Some(texture.resize_exact(r.w, r.h, filter))
+        } else {
+            Some(texture.resize(r.w, r.h, filter))
+        }
+    }
+}
+
+/// Check that a sprite is not empty and inside its atlas
+fn check_sprite(atlas: &image::DynamicImage, name: &str, rect: Rect) -> Result<(), TextureError> {
+    let (width, height) = atlas.dimensions();
+    let in_bounds = rect.width > 0
+        && rect.height > 0
+        && u64::from(rect.x) + u64::from(rect.width) <= u64::from(width)
+        && u64::from(rect.y) + u64::from(rect.height) <= u64::from(height);
+    if !in_bounds {
+        return Err(TextureError::InvalidSprite(String::from(name)));
+    }
+
+    Ok(())
+}
+
+/// Parse a manifest into (line number, name, value) triples
+fn parse_manifest(manifest: &str) -> Result<Vec<(usize, &str, &str)>, TextureError> {
+    manifest
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            let mut iter = line.splitn(2, '=');
+            let name = iter.next().map(str::trim).unwrap_or_default();
+            match iter.next().map(str::trim) {
+                Some(value) if !name.is_empty() && !value.is_empty() => Ok((i + 1, name, value)),
+                _ => Err(TextureError::InvalidManifestLine(i + 1)),
+            }
+        })
+        .collect()
+}
+
+/// Errors that may occur while loading a texture pack
+#[derive(Debug)]
+pub enum TextureError {
+    Io(std::io::Error),
+    Zip(zip::result::ZipError),
+    Image(image::ImageError),
+
+    InvalidManifestEncoding,
+    /// A line of the manifest is not of the form `name = file`. Line numbers start at 1.
+    InvalidManifestLine(usize),
+    MissingFile(String),
+    /// A file in a zip pack is bigger than MAX_ZIP_FILE_SIZE
+    FileTooLarge(String),
+    /// A path in the manifest is absolute or leaves the pack
+    InvalidPath(String),
+    UnknownTextureName(String),
+    /// A sprite is empty or not inside its atlas
+    InvalidSprite(String),
+}
+
+impl From<std::io::Error> for TextureError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<zip::result::ZipError> for TextureError {
+    fn from(e: zip::result::ZipError) -> Self {
+        Self::Zip(e)
+    }
+}
+
+impl From<image::ImageError> for TextureError {
+    fn from(e: image::ImageError) -> Self {
+        Self::Image(e)
+    }
+}
@@ -0,0 +1,649 @@
+/// Estimating how hard levels are
+pub mod difficulty;
+/// Estimating which cells the player can get to, without simulating physics
+pub mod reach;
+/// Searching for inputs that beat a level
+pub mod solver;
+
+use crate::{
+    block::{
+        Block,
+        Direction,
+    },
+    circuit::Circuits,
+    grid::GridError,
+    pipe::Pipes,
+};
+use std::collections::HashMap;
+
+/// The version of the rules of the simulation. Changes whenever a run of a level could play out differently.
+pub const SIM_VERSION: u32 = 3;
+/// The number of simulation steps in a second
+pub const TICKS_PER_SECOND: u32 = 60;
+/// The number of position units in a cell. Positions are integers, so simulations are deterministic.
+pub const UNITS_PER_CELL: i32 = 256;
+
+/// The width of the player, in units. Narrower than a cell, so one cell gaps can be entered.
+pub const PLAYER_WIDTH: i32 = 192;
+/// The height of the player, in units
+pub const PLAYER_HEIGHT: i32 = 224;
+/// The horizontal speed of the player, in units per tick
+pub const MOVE_SPEED: i32 = 24;
+/// The speed gained when falling, in units per tick per tick
+pub const GRAVITY: i32 = 3;
+/// The fastest the player can fall, in units per tick. Less than the player's size, so nothing is skipped.
+pub const MAX_FALL_SPEED: i32 = 64;
+/// The upward speed of a jump, in units per tick. Jumps are a little over 2 cells high.
+pub const JUMP_SPEED: i32 = 60;
+
+/// The buttons held during a tick
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Input {
+    pub left: bool,
+    pub right: bool,
+    pub jump: bool,
+    /// Dig through the floor. Only acts on the tick it is pressed.
+    pub burrow: bool,
+    /// Place or return to the recall point. Only acts on the tick it is pressed.
+    pub recall: bool,
+}
+
+impl Input {
+    /// No buttons
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Hold left
+    pub fn left() -> Self {
+        Self {
+            left: true,
+            ..Self::default()
+        }
+    }
+
+    /// Hold right
+    pub fn right() -> Self {
+        Self {
+            right: true,
+            ..Self::default()
+        }
+    }
+
+    /// Hold jump
+    pub fn jump() -> Self {
+        Self {
+            jump: true,
+            ..Self::default()
+        }
+    }
+
+    /// Press burrow
+    pub fn burrow() -> Self {
+        Self {
+            burrow: true,
+            ..Self::default()
+        }
+    }
+
+    /// Press recall
+    pub fn recall() -> Self {
+        Self {
+            recall: true,
+            ..Self::default()
+        }
+    }
+
+    /// Also hold jump
+    pub fn with_jump(mut self) -> Self {
+        self.jump = true;
+        self
+    }
+}
+
+/// Whether a simulation is still running
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Status {
+    Playing,
+    /// The player reached an Exit
+    Exited,
+    /// The player reached a SecretExit
+    SecretExited,
+}
+
+impl Status {
+    /// Returns true if the player reached any exit
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, Self::Playing)
+    }
+}
+
+/// The player. Positions are the top left corner of the player, in units.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Player {
+    pub x: i32,
+    pub y: i32,
+    /// Vertical speed in units per tick. Positive is down.
+    pub vy: i32,
+    /// True if the player is standing on something
+    pub on_ground: bool,
+}
+
+impl Player {
+    /// Get the (x, y) of the cell the center of the player is in
+    pub fn cell(&self) -> (i32, i32) {
+        (
+            (self.x + PLAYER_WIDTH / 2).div_euclid(UNITS_PER_CELL),
+            (self.y + PLAYER_HEIGHT / 2).div_euclid(UNITS_PER_CELL),
+        )
+    }
+
+    /// Place the player at the bottom center of a cell
+    fn place(&mut self, x: i32, y: i32) {
+        self.x = x * UNITS_PER_CELL + (UNITS_PER_CELL - PLAYER_WIDTH) / 2;
+        self.y = (y + 1) * UNITS_PER_CELL - PLAYER_HEIGHT;
+        self.vy = 0;
+    }
+}
+
+/// A set of cells of a level
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct CellSet([u64; crate::LEVEL_SIZE.div_ceil(64)]);
+
+impl CellSet {
+    /// Make an empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true if a cell index is in the set
+    pub fn contains(&self, i: usize) -> bool {
+        self.0
+            .get(i / 64)
+            .is_some_and(|bits| bits & (1 << (i % 64)) != 0)
+    }
+
+    /// Add a cell index to the set
+    pub fn insert(&mut self, i: usize) {
+        if let Some(bits) = self.0.get_mut(i / 64) {
+            *bits |= 1 << (i % 64);
+        }
+    }
+
+    /// Add a cell index if it is missing from the set, or remove it if it is there
+    pub fn toggle(&mut self, i: usize) {
+        if let Some(bits) = self.0.get_mut(i / 64) {
+            *bits ^= 1 << (i % 64);
+        }
+    }
+
+    /// Iterate over the cell indices in the set, in order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..crate::LEVEL_SIZE).filter(move |i| self.contains(*i))
+    }
+
+    /// The number of cells in the set
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|bits| bits.count_ones() as usize).sum()
+    }
+
+    /// Returns true if the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Everything that changes while a level is played
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct State {
+    pub player: Player,
+    /// The number of steps simulated
+    pub tick: u32,
+    pub status: Status,
+    /// Keys held
+    pub keys: u8,
+    /// Toggle blocks that have been flipped
+    pub flipped: CellSet,
+    /// Burrow power-ups held
+    pub burrows: u8,
+    /// Recall power-ups held
+    pub recalls: u8,
+    /// Where the next recall returns to, as the player's (x, y)
+    pub recall_point: Option<(i32, i32)>,
+    /// Cells whose blocks are gone: collected keys and power-ups, and opened locks
+    pub cleared: CellSet,
+    /// The input of the last tick, used to only act on presses
+    pub previous_input: Input,
+}
+
+/// The parts of a level that don't change while it is played
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level {
+    blocks: Vec<Block>,
+    spawn: (usize, usize),
+    /// Where every PipeIn leads
+    pipes: HashMap<usize, usize>,
+    /// The toggle blocks every switch flips
+    switches: HashMap<usize, Vec<usize>>,
+}
+
+impl Level {
+    /// Prepare a level for simulation. The player starts at the first Player block.
+    pub fn new(blocks: &[Block]) -> Result<Self, SimError> {
+        if blocks.len() != crate::LEVEL_SIZE {
+            return Err(SimError::InvalidLength(blocks.len()));
+        }
+
+        let spawn = blocks
+            .iter()
+            .position(|block| *block == Block::Player)
+            .ok_or(SimError::MissingPlayer)?;
+
+        let circuits = Circuits::analyze(blocks)?;
+        let switches = circuits
+            .circuits()
+            .iter()
+            .flat_map(|circuit| circuit.switches.iter())
+            .map(|i| (*i, circuits.affected(*i).to_vec()))
+            .collect();
+
+        let pipes = Pipes::analyze(blocks)?
+            .networks
+            .iter()
+            .flat_map(|network| network.pairs.iter())
+            .filter_map(|(entrance, exit)| Some((*entrance, (*exit)?)))
+            .collect();
+
+        Ok(Self {
+            blocks: blocks.to_vec(),
+            spawn: (spawn % crate::LEVEL_WIDTH, spawn / crate::LEVEL_WIDTH),
+            pipes,
+            switches,
+        })
+    }
+
+    /// The blocks of this level
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    /// The (x, y) cell the player starts in
+    pub fn spawn(&self) -> (usize, usize) {
+        self.spawn
+    }
+
+    /// Get the cell index a PipeIn leads to. None if the cell isn't a PipeIn, or its network has no PipeOut.
+    pub fn pipe_exit(&self, i: usize) -> Option<usize> {
+        self.pipes.get(&i).copied()
+    }
+
+    /// Get the toggle blocks a switch flips. Empty if the cell isn't a switch.
+    pub fn switch_toggles(&self, i: usize) -> &[usize] {
+        self.switches.get(&i).map_or(&[], Vec::as_slice)
+    }
+
+    /// Get the index of a cell. Cells outside of the level are None.
+    pub(crate) fn index(x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= crate::LEVEL_WIDTH as i32 || y >= crate::LEVEL_HEIGHT as i32 {
+            return None;
+        }
+
+        Some(y as usize * crate::LEVEL_WIDTH + x as usize)
+    }
+
+    /// Get the block in a cell. Cells outside of the level are None.
+    pub fn get(&self, x: i32, y: i32) -> Option<&Block> {
+        self.blocks.get(Self::index(x, y)?)
+    }
+
+    /// Get the block in a cell in a state, where cleared cells are empty. Cells outside of the level are None.
+    pub fn get_in(&self, state: &State, x: i32, y: i32) -> Option<&Block> {
+        const EMPTY: &Block = &Block::Empty;
+        let i = Self::index(x, y)?;
+        if state.cleared.contains(i) {
+            Some(EMPTY)
+        } else {
+            self.blocks.get(i)
+        }
+    }
+
+    /// The state at the start of the level, standing at the bottom of the spawn cell
+    pub fn initial_state(&self) -> State {
+        let (x, y) = self.spawn;
+        let mut state = State {
+            player: Player {
+                x: 0,
+                y: 0,
+                vy: 0,
+                on_ground: false,
+            },
+            tick: 0,
+            status: Status::Playing,
+            keys: 0,
+            flipped: CellSet::new(),
+            burrows: 0,
+            recalls: 0,
+            recall_point: None,
+            cleared: CellSet::new(),
+            previous_input: Input::none(),
+        };
+        state.player.place(x as i32, y as i32);
+        state.player.on_ground = self.is_supported(&state);
+        state
+    }
+
+    /// Simulate one tick. Finished states don't change.
+    ///
+    /// Blocks act like this:
+    /// Block, Dark, Lock, PipeSolid, SwitchCeiling, and solid toggle blocks stop the player from every side.
+    /// Scaffold can be landed on from above and passed from every other side.
+    /// A OneWayWall can only be passed moving in its direction.
+    /// Touching a Lock while holding a key opens it. Keys and power-ups are collected by touching them.
+    /// Entering a Switch or jumping into a SwitchCeiling flips the toggle blocks of its circuit. See [`Circuits`].
+    /// Entering a PipeIn moves the player to the PipeOut it leads to. See [`Pipes`].
+    /// Burrow moves the player through the block under them, if there is room below it.
+    /// The first recall marks the player's position, and the second returns to it and uses up the power-up.
+    pub fn step(&self, state: &State, input: Input) -> State {
+        let mut state = state.clone();
+        if state.status.is_finished() {
+            return state;
+        }
+
+        let old_cell = state.player.cell();
+        let previous = state.previous_input;
+        if input.burrow && !previous.burrow {
+            self.burrow(&mut state);
+        }
+        if input.recall && !previous.recall && state.recalls > 0 {
+            match state.recall_point.take() {
+                Some((x, y)) => {
+                    state.player.x = x;
+                    state.player.y = y;
+                    state.player.vy = 0;
+                    state.recalls -= 1;
+                }
+                None => state.recall_point = Some((state.player.x, state.player.y)),
+            }
+        }
+
+        if input.jump && state.player.on_ground {
+            state.player.vy = -JUMP_SPEED;
+        }
+
+        let dx = (i32::from(input.right) - i32::from(input.left)) * MOVE_SPEED;
+        self.move_x(&mut state, dx);
+
+        state.player.vy = (state.player.vy + GRAVITY).min(MAX_FALL_SPEED);
+        let vy = state.player.vy;
+        self.move_y(&mut state, vy);
+
+        self.touch(&mut state, old_cell);
+        state.player.on_ground = self.is_supported(&state);
+        state.previous_input = input;
+        state.tick += 1;
+
+        state
+    }
+
+    /// Returns true if a cell stops the player from moving into it in a direction. Cells outside of the level are solid.
+    pub fn blocks_movement(&self, state: &State, x: i32, y: i32, direction: &Direction) -> bool {
+        match self.get_in(state, x, y) {
+            None => true,
+            Some(block) => match block {
+                Block::Scaffold => *direction == Direction::Down,
+                Block::OneWayWall {
+                    direction: passable,
+                } => passable != direction,
+                Block::ToggleBlock { solid } => {
+                    *solid != Self::index(x, y).is_some_and(|i| state.flipped.contains(i))
+                }
+                block => is_solid_block(block),
+            },
+        }
+    }
+
+    /// Move the player horizontally, stopping at walls. Only cells the player enters can stop it.
+    fn move_x(&self, state: &mut State, dx: i32) {
+        if dx == 0 {
+            return;
+        }
+
+        let (old_left, old_right) = cell_span(state.player.x, PLAYER_WIDTH);
+        state.player.x += dx;
+        let (left, right) = cell_span(state.player.x, PLAYER_WIDTH);
+        let (top, bottom) = cell_span(state.player.y, PLAYER_HEIGHT);
+
+        let (column, direction) = if dx > 0 {
+            (right, Direction::Right)
+        } else {
+            (left, Direction::Left)
+        };
+        if column == old_left || column == old_right {
+            return;
+        }
+
+        let blocked: Vec<_> = (top..=bottom)
+            .filter(|y| self.blocks_movement(state, column, *y, &direction))
+            .map(|y| (column, y))
+            .collect();
+        if !blocked.is_empty() {
+            state.player.x = if dx > 0 {
+                column * UNITS_PER_CELL - PLAYER_WIDTH
+            } else {
+                (column + 1) * UNITS_PER_CELL
+            };
+            self.bump(state, &blocked, &direction);
+        }
+    }
+
+    /// Move the player vertically, landing on floors and stopping at ceilings. Only cells the player enters can stop it.
+    fn move_y(&self, state: &mut State, dy: i32) {
+        if dy == 0 {
+            return;
+        }
+
+        let (old_top, old_bottom) = cell_span(state.player.y, PLAYER_HEIGHT);
+        state.player.y += dy;
+        let (top, bottom) = cell_span(state.player.y, PLAYER_HEIGHT);
+        let (left, right) = cell_span(state.player.x, PLAYER_WIDTH);
+
+        let (row, direction) = if dy > 0 {
+            (bottom, Direction::Down)
+        } else {
+            (top, Direction::Up)
+        };
+        if row == old_top || row == old_bottom {
+            return;
+        }
+
+        let blocked: Vec<_> = (left..=right)
+            .filter(|x| self.blocks_movement(state, *x, row, &direction))
+            .map(|x| (x, row))
+            .collect();
+        if !blocked.is_empty() {
+            state.player.y = if dy > 0 {
+                row * UNITS_PER_CELL - PLAYER_HEIGHT
+            } else {
+                (row + 1) * UNITS_PER_CELL
+            };
+            state.player.vy = 0;
+            self.bump(state, &blocked, &direction);
+        }
+    }
+
+    /// Handle the player running into cells: opening locks and hitting ceiling switches
+    fn bump(&self, state: &mut State, cells: &[(i32, i32)], direction: &Direction) {
+        for (x, y) in cells.iter().copied() {
+            match self.get_in(state, x, y) {
+                Some(Block::Lock) if state.keys > 0 => {
+                    state.keys -= 1;
+                    if let Some(i) = Self::index(x, y) {
+                        state.cleared.insert(i);
+                    }
+                }
+                Some(Block::SwitchCeiling) if *direction == Direction::Up => {
+                    if let Some(i) = Self::index(x, y) {
+                        self.activate(state, i);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Handle the cell the center of the player is in: pickups, switches, pipes, and exits
+    fn touch(&self, state: &mut State, old_cell: (i32, i32)) {
+        let (x, y) = state.player.cell();
+        let entered = (x, y) != old_cell;
+        let i = match Self::index(x, y) {
+            Some(i) => i,
+            None => return,
+        };
+
+        match self.get_in(state, x, y) {
+            Some(Block::Key) => {
+                state.keys = state.keys.saturating_add(1);
+                state.cleared.insert(i);
+            }
+            Some(Block::PowerUpBurrow) => {
+                state.burrows = state.burrows.saturating_add(1);
+                state.cleared.insert(i);
+            }
+            Some(Block::PowerUpRecall) => {
+                state.recalls = state.recalls.saturating_add(1);
+                state.cleared.insert(i);
+            }
+            Some(Block::Switch) if entered => self.activate(state, i),
+            Some(Block::PipeIn) if entered => {
+                if let Some(exit) = self.pipe_exit(i) {
+                    let (exit_x, exit_y) = (exit % crate::LEVEL_WIDTH, exit / crate::LEVEL_WIDTH);
+                    state.player.place(exit_x as i32, exit_y as i32);
+                }
+            }
+            Some(Block::Exit) => state.status = Status::Exited,
+            Some(Block::SecretExit) => state.status = Status::SecretExited,
+            _ => {}
+        }
+    }
+
+    /// Flip the toggle blocks of a switch
+    fn activate(&self, state: &mut State, switch: usize) {
+        for i in self.switch_toggles(switch) {
+            state.flipped.toggle(*i);
+        }
+    }
+
+    /// Dig through the block under the center of the player, if they are standing on it and there is room below it
+    fn burrow(&self, state: &mut State) {
+        if state.burrows == 0 || !state.player.on_ground {
+            return;
+        }
+
+        let (x, _) = state.player.cell();
+        let below = (state.player.y + PLAYER_HEIGHT).div_euclid(UNITS_PER_CELL);
+        let diggable = self.get_in(state, x, below).is_some()
+            && self.blocks_movement(state, x, below, &Direction::Down);
+        let room = self.get_in(state, x, below + 1).is_some()
+            && !self.blocks_movement(state, x, below + 1, &Direction::Down);
+        if diggable && room {
+            state.player.place(x, below + 1);
+            state.burrows -= 1;
+        }
+    }
+
+    /// Returns true if the player is standing on something
+    fn is_supported(&self, state: &State) -> bool {
+        let bottom = state.player.y + PLAYER_HEIGHT;
+        if bottom.rem_euclid(UNITS_PER_CELL) != 0 {
+            return false;
+        }
+
+        let below = bottom.div_euclid(UNITS_PER_CELL);
+        let (left, right) = cell_span(state.player.x, PLAYER_WIDTH);
+        (left..=right).any(|x| self.blocks_movement(state, x, below, &Direction::Down))
+    }
+}
+
+/// Returns true if a block stops the player from every side, no matter the state of the level
+pub fn is_solid_block(block: &Block) -> bool {
+    matches!(
+        block,
+        Block::Block
+            | Block::Dark
+            | Block::Lock
+            | Block::PipeSolid
+            | Block::SwitchCeiling
+            | Block::ToggleBlock { solid: true }
+    )
+}
+
+/// Get the first and last cell covered by a span of units
+fn cell_span(start: i32, len: i32) -> (i32, i32) {
+    (
+        start.div_euclid(UNITS_PER_CELL),
+        (start + len - 1).div_euclid(UNITS_PER_CELL),
+    )
+}
+
+/// A level being played
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    level: Level,
+    state: State,
+}
+
+impl Simulation {
+    /// Start playing a level
+    pub fn new(blocks: &[Block]) -> Result<Self, SimError> {
+        let level = Level::new(blocks)?;
+        let state = level.initial_state();
+        Ok(Self { level, state })
+    }
+
+    /// The level being played
+    pub fn level(&self) -> &Level {
+        &self.level
+    }
+
+    /// The current state
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    /// Simulate one tick
+    pub fn step(&mut self, input: Input) -> &State {
+        self.state = self.level.step(&self.state, input);
+        &self.state
+    }
+
+    /// Simulate a tick for every input, stopping early if the player reaches an exit
+    pub fn run<I>(&mut self, inputs: I) -> &State
+    where
+        I: IntoIterator<Item = Input>,
+    {
+        for input in inputs {
+            if self.state.status.is_finished() {
+                break;
+            }
+            self.step(input);
+        }
+
+        &self.state
+    }
+}
+
+/// Errors that may occur while starting a simulation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimError {
+    InvalidLength(usize),
+    /// There is no Player block to start at
+    MissingPlayer,
+}
+
+impl From<GridError> for SimError {
+    fn from(e: GridError) -> Self {
+        match e {
+            GridError::InvalidLength(len) => Self::InvalidLength(len),
+        }
+    }
+}
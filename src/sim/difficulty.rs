@@ -0,0 +1,313 @@
+use crate::{
+    block::Block,
+    sim::{
+        solver::{
+            solve,
+            SolveResult,
+            SolverOptions,
+        },
+        Input,
+        Level,
+        SimError,
+        TICKS_PER_SECOND,
+    },
+};
+
+/// What a solution of a level involves
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Features {
+    /// The length of the solution
+    pub ticks: u32,
+    pub jumps: u32,
+    /// Jumps that fail if started a few ticks earlier or later
+    pub precise_jumps: u32,
+    /// Locks opened with keys
+    pub locks: u32,
+    /// Switches activated
+    pub switches: u32,
+    /// Burrow power-ups used
+    pub burrows: u32,
+    /// Recall power-ups used
+    pub recalls: u32,
+}
+
+/// Points per feature of a solution
+#[derive(Debug, Clone, PartialEq)]
+pub struct DifficultyWeights {
+    pub per_second: f32,
+    pub per_jump: f32,
+    pub per_precise_jump: f32,
+    pub per_lock: f32,
+    pub per_switch: f32,
+    pub per_burrow: f32,
+    pub per_recall: f32,
+}
+
+impl DifficultyWeights {
+    /// Hand picked weights that rank mechanics in the order walking, jumping, keys, switches, burrow and recall.
+    ///
+    /// They are not calibrated against real levels, since there is no campaign of solvable levels to calibrate on.
+    /// Use [`calibrate`] to fit weights to levels in a known order.
+    pub fn new() -> Self {
+        Self {
+            per_second: 1.0,
+            per_jump: 0.5,
+            per_precise_jump: 2.0,
+            per_lock: 4.0,
+            per_switch: 4.0,
+            per_burrow: 8.0,
+            per_recall: 10.0,
+        }
+    }
+
+    /// Get the (name, points) of every feature
+    pub fn breakdown(&self, features: &Features) -> Vec<(&'static str, f32)> {
+        vec![
+            (
+                "length",
+                self.per_second * features.ticks as f32 / TICKS_PER_SECOND as f32,
+            ),
+            ("jumps", self.per_jump * features.jumps as f32),
+            (
+                "precise jumps",
+                self.per_precise_jump * features.precise_jumps as f32,
+            ),
+            ("locks", self.per_lock * features.locks as f32),
+            ("switches", self.per_switch * features.switches as f32),
+            ("burrows", self.per_burrow * features.burrows as f32),
+            ("recalls", self.per_recall * features.recalls as f32),
+        ]
+    }
+
+    /// Get the total points of some features
+    pub fn score(&self, features: &Features) -> f32 {
+        self.breakdown(features)
+            .iter()
+            .map(|(_, points)| points)
+            .sum()
+    }
+
+    /// Get every weight. Listing the fields without `..` keeps this in step with the struct.
+    fn values_mut(&mut self) -> Vec<&mut f32> {
+        let Self {
+            per_second,
+            per_jump,
+            per_precise_jump,
+            per_lock,
+            per_switch,
+            per_burrow,
+            per_recall,
+        } = self;
+        vec![
+            per_second,
+            per_jump,
+            per_precise_jump,
+            per_lock,
+            per_switch,
+            per_burrow,
+            per_recall,
+        ]
+    }
+}
+
+impl Default for DifficultyWeights {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Options for estimating difficulty
+#[derive(Debug, Clone, Default)]
+pub struct DifficultyOptions {
+    pub solver: SolverOptions,
+    pub weights: DifficultyWeights,
+    /// How many ticks a jump may be moved and still count as not precise. 0 counts no jumps as precise.
+    pub jump_tolerance: u32,
+}
+
+impl DifficultyOptions {
+    /// Default DifficultyOptions.
+    pub fn new() -> Self {
+        Self {
+            solver: SolverOptions::new(),
+            weights: DifficultyWeights::new(),
+            jump_tolerance: 3,
+        }
+    }
+
+    /// The options of the solver used to find a solution
+    pub fn solver(mut self, solver: SolverOptions) -> Self {
+        self.solver = solver;
+        self
+    }
+
+    /// The points per feature
+    pub fn weights(mut self, weights: DifficultyWeights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// How many ticks a jump may be moved and still count as not precise
+    pub fn jump_tolerance(mut self, jump_tolerance: u32) -> Self {
+        self.jump_tolerance = jump_tolerance;
+        self
+    }
+}
+
+/// How hard a level is
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difficulty {
+    pub score: f32,
+    pub features: Features,
+    /// The (name, points) of every feature, adding up to score
+    pub breakdown: Vec<(&'static str, f32)>,
+}
+
+/// Estimate how hard a level is from what its solution involves
+pub fn estimate(
+    blocks: &[Block],
+    options: &DifficultyOptions,
+) -> Result<Difficulty, DifficultyError> {
+    let solution = match solve(blocks, &options.solver)? {
+        SolveResult::Solved(solution) => solution,
+        SolveResult::Unsolvable { .. } => return Err(DifficultyError::Unsolvable),
+        SolveResult::GaveUp { .. } => return Err(DifficultyError::GaveUp),
+    };
+    let features = features(blocks, &solution.inputs, options.jump_tolerance)?;
+
+    Ok(Difficulty {
+        score: options.weights.score(&features),
+        breakdown: options.weights.breakdown(&features),
+        features,
+    })
+}
+
+/// Find what a run of a level involves by playing it
+pub fn features(
+    blocks: &[Block],
+    inputs: &[Input],
+    jump_tolerance: u32,
+) -> Result<Features, SimError> {
+    let level = Level::new(blocks)?;
+    let mut features = Features::default();
+    let mut jump_starts = Vec::new();
+
+    let mut state = level.initial_state();
+    for (tick, input) in inputs.iter().copied().enumerate() {
+        if state.status.is_finished() {
+            break;
+        }
+
+        let next = level.step(&state, input);
+        if input.jump && state.player.on_ground {
+            features.jumps += 1;
+            jump_starts.push(tick);
+        }
+        if next.keys < state.keys {
+            features.locks += 1;
+        }
+        if next.flipped != state.flipped {
+            features.switches += 1;
+        }
+        if next.burrows < state.burrows {
+            features.burrows += 1;
+        }
+        if next.recalls < state.recalls {
+            features.recalls += 1;
+        }
+        state = next;
+    }
+    features.ticks = state.tick;
+
+    if jump_tolerance > 0 && state.status.is_finished() {
+        let status = state.status;
+        let tolerance = jump_tolerance as usize;
+        // Moved jumps may take a little longer, so keep going with the last input
+        let last = inputs.last().copied().unwrap_or_default();
+        let still_finishes = |inputs: &[Input]| {
+            let mut state = level.initial_state();
+            let slack = std::iter::repeat_n(last, 2 * tolerance);
+            for input in inputs.iter().copied().chain(slack) {
+                state = level.step(&state, input);
+                if state.status.is_finished() {
+                    break;
+                }
+            }
+            state.status == status
+        };
+
+        for start in jump_starts {
+            // Press jump earlier, then release it until later
+            let mut early = inputs.to_vec();
+            for input in early[start.saturating_sub(tolerance)..start].iter_mut() {
+                input.jump = true;
+            }
+            let mut late = inputs.to_vec();
+            let end = (start + tolerance).min(late.len());
+            for input in late[start..end].iter_mut() {
+                input.jump = false;
+            }
+
+            if !still_finishes(&early) || !still_finishes(&late) {
+                features.precise_jumps += 1;
+            }
+        }
+    }
+
+    Ok(features)
+}
+
+/// Fit weights so levels score in the order given, easiest first.
+///
+/// Starting from the default weights, every weight is repeatedly scaled up or down while that
+/// reduces the number of pairs of levels that score out of order. Returns the weights and the number of pairs left out of order.
+pub fn calibrate(ordered: &[Features]) -> (DifficultyWeights, usize) {
+    let inversions = |weights: &DifficultyWeights| {
+        let scores: Vec<f32> = ordered.iter().map(|f| weights.score(f)).collect();
+        let mut count = 0;
+        for (i, a) in scores.iter().enumerate() {
+            count += scores[i + 1..].iter().filter(|b| *b <= a).count();
+        }
+        count
+    };
+
+    let mut weights = DifficultyWeights::new();
+    let mut best = inversions(&weights);
+    let count = weights.values_mut().len();
+    for _ in 0..32 {
+        let mut improved = false;
+        for n in 0..count {
+            for factor in [2.0, 0.5].iter() {
+                let mut candidate = weights.clone();
+                *candidate.values_mut()[n] *= factor;
+                let count = inversions(&candidate);
+                if count < best {
+                    weights = candidate;
+                    best = count;
+                    improved = true;
+                }
+            }
+        }
+        if best == 0 || !improved {
+            break;
+        }
+    }
+
+    (weights, best)
+}
+
+/// Errors that may occur while estimating difficulty
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DifficultyError {
+    Sim(SimError),
+    /// The solver proved there is no solution
+    Unsolvable,
+    /// The solver ran out of states before finding a solution
+    GaveUp,
+}
+
+impl From<SimError> for DifficultyError {
+    fn from(e: SimError) -> Self {
+        Self::Sim(e)
+    }
+}
@@ -0,0 +1,264 @@
+use crate::{
+    block::{
+        Block,
+        Direction,
+    },
+    sim::{
+        is_solid_block,
+        CellSet,
+        Level,
+        SimError,
+    },
+};
+use std::collections::VecDeque;
+
+/// The number of cells a jump rises
+pub const JUMP_HEIGHT: i32 = 2;
+/// The number of cells the player can move sideways during a jump
+pub const JUMP_DISTANCE: i32 = 3;
+
+/// The cells the player can get to, found by moving between cells instead of simulating physics.
+///
+/// The player stands in a cell above something solid. From there it can jump up to JUMP_HEIGHT cells,
+/// move up to JUMP_DISTANCE cells sideways at any height of the jump, and fall.
+/// The analysis is optimistic: it is much faster than the solver, but may find cells the player can't really get to.
+///
+/// Locks open when bumped, as long as more keys can be reached than locks have been opened.
+/// Once a switch can be reached, the toggle blocks it flips count as both solid and open.
+/// Pipes are followed to their exit. Burrow and recall are not used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reachability {
+    /// Cells the player can pass through
+    pub cells: CellSet,
+    /// Locks the player can open
+    pub opened: CellSet,
+    /// Toggle blocks the player can flip
+    pub flippable: CellSet,
+    /// True if an Exit can be reached
+    pub exit: bool,
+    /// True if a SecretExit can be reached
+    pub secret_exit: bool,
+    /// The cell indices of keys, switches, and power-ups the player can't get to
+    pub unreachable: Vec<usize>,
+}
+
+impl Reachability {
+    /// Find the cells the player can get to in a level
+    pub fn analyze(blocks: &[Block]) -> Result<Self, SimError> {
+        let level = Level::new(blocks)?;
+        let mut analysis = Analysis {
+            level: &level,
+            opened: CellSet::new(),
+            flippable: CellSet::new(),
+        };
+
+        // Opening locks and flipping toggles only adds cells, so repeat until nothing changes
+        let search = loop {
+            let search = analysis.search();
+            let keys = search
+                .cells
+                .iter()
+                .filter(|i| blocks[*i] == Block::Key)
+                .count();
+
+            let mut changed = false;
+            let switches = search
+                .cells
+                .iter()
+                .filter(|i| blocks[*i] == Block::Switch)
+                .chain(
+                    search
+                        .bumped
+                        .iter()
+                        .filter(|i| blocks[*i] == Block::SwitchCeiling),
+                );
+            for switch in switches {
+                for i in level.switch_toggles(switch) {
+                    if !analysis.flippable.contains(*i) {
+                        analysis.flippable.insert(*i);
+                        changed = true;
+                    }
+                }
+            }
+            for i in search.bumped.iter() {
+                if blocks[i] == Block::Lock && analysis.opened.len() < keys {
+                    analysis.opened.insert(i);
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break search;
+            }
+        };
+
+        let unreachable = blocks
+            .iter()
+            .enumerate()
+            .filter(|(i, block)| match block {
+                Block::Key | Block::Switch | Block::PowerUpBurrow | Block::PowerUpRecall => {
+                    !search.cells.contains(*i)
+                }
+                Block::SwitchCeiling => !search.bumped.contains(*i),
+                _ => false,
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        let reaches = |target: Block| search.cells.iter().any(|i| blocks[i] == target);
+        Ok(Self {
+            exit: reaches(Block::Exit),
+            secret_exit: reaches(Block::SecretExit),
+            cells: search.cells,
+            opened: analysis.opened,
+            flippable: analysis.flippable,
+            unreachable,
+        })
+    }
+
+    /// Returns true if the player can get to a cell
+    pub fn is_reachable(&self, x: usize, y: usize) -> bool {
+        x < crate::LEVEL_WIDTH
+            && y < crate::LEVEL_HEIGHT
+            && self.cells.contains(y * crate::LEVEL_WIDTH + x)
+    }
+}
+
+/// The level with some locks opened and some toggle blocks flippable
+struct Analysis<'a> {
+    level: &'a Level,
+    opened: CellSet,
+    flippable: CellSet,
+}
+
+/// What one search found
+struct Search {
+    /// Cells passed through
+    cells: CellSet,
+    /// Locks and ceiling switches run into
+    bumped: CellSet,
+}
+
+impl Analysis<'_> {
+    /// Get the block in a cell, where opened locks are empty. Cells outside of the level are None.
+    fn get(&self, x: i32, y: i32) -> Option<&Block> {
+        let i = Level::index(x, y)?;
+        if self.opened.contains(i) {
+            Some(&Block::Empty)
+        } else {
+            self.level.blocks().get(i)
+        }
+    }
+
+    /// Returns true if the player can move into a cell in a direction
+    fn passable(&self, x: i32, y: i32, direction: &Direction) -> bool {
+        match self.get(x, y) {
+            None => false,
+            Some(Block::Scaffold) => *direction != Direction::Down,
+            Some(Block::OneWayWall {
+                direction: passable,
+            }) => passable == direction,
+            Some(Block::ToggleBlock { solid }) => !solid || self.is_flippable(x, y),
+            Some(block) => !is_solid_block(block),
+        }
+    }
+
+    /// Returns true if the player can stand on a cell
+    fn supports(&self, x: i32, y: i32) -> bool {
+        match self.get(x, y) {
+            Some(Block::ToggleBlock { solid }) => *solid || self.is_flippable(x, y),
+            _ => !self.passable(x, y, &Direction::Down),
+        }
+    }
+
+    fn is_flippable(&self, x: i32, y: i32) -> bool {
+        Level::index(x, y).is_some_and(|i| self.flippable.contains(i))
+    }
+
+    fn search(&self) -> Search {
+        let mut search = Search {
+            cells: CellSet::new(),
+            bumped: CellSet::new(),
+        };
+        let mut standing = CellSet::new();
+        let mut queue = VecDeque::new();
+
+        let (x, y) = self.level.spawn();
+        self.fall(&mut search, &mut standing, &mut queue, x as i32, y as i32);
+
+        while let Some((x, y)) = queue.pop_front() {
+            for height in 0..=JUMP_HEIGHT {
+                let y = y - height;
+                if height > 0 && !self.passable(x, y, &Direction::Up) {
+                    self.bump(&mut search, x, y, &Direction::Up);
+                    break;
+                }
+                self.fall(&mut search, &mut standing, &mut queue, x, y);
+
+                for (step, direction) in [(-1, Direction::Left), (1, Direction::Right)].iter() {
+                    for distance in 1..=JUMP_DISTANCE {
+                        let x = x + step * distance;
+                        if !self.passable(x, y, direction) {
+                            self.bump(&mut search, x, y, direction);
+                            break;
+                        }
+                        self.fall(&mut search, &mut standing, &mut queue, x, y);
+                    }
+                }
+            }
+        }
+
+        search
+    }
+
+    /// Pass through a cell and fall from it, queueing every cell the player can stand in on the way down
+    fn fall(
+        &self,
+        search: &mut Search,
+        standing: &mut CellSet,
+        queue: &mut VecDeque<(i32, i32)>,
+        x: i32,
+        mut y: i32,
+    ) {
+        loop {
+            let i = match Level::index(x, y) {
+                Some(i) => i,
+                None => return,
+            };
+            let first_visit = !search.cells.contains(i);
+            search.cells.insert(i);
+
+            if first_visit && self.get(x, y) == Some(&Block::PipeIn) {
+                if let Some(exit) = self.level.pipe_exit(i) {
+                    let (exit_x, exit_y) = (exit % crate::LEVEL_WIDTH, exit / crate::LEVEL_WIDTH);
+                    self.fall(search, standing, queue, exit_x as i32, exit_y as i32);
+                    return;
+                }
+            }
+
+            if self.supports(x, y + 1) && !standing.contains(i) {
+                standing.insert(i);
+                queue.push_back((x, y));
+            }
+            if !self.passable(x, y + 1, &Direction::Down) {
+                self.bump(search, x, y + 1, &Direction::Down);
+                return;
+            }
+            y += 1;
+        }
+    }
+
+    /// Note running into a lock, or into a ceiling switch from below
+    fn bump(&self, search: &mut Search, x: i32, y: i32, direction: &Direction) {
+        if let (Some(i), Some(block)) = (Level::index(x, y), self.get(x, y)) {
+            let bumped = match block {
+                Block::Lock => true,
+                Block::SwitchCeiling => *direction == Direction::Up,
+                _ => false,
+            };
+            if bumped {
+                search.bumped.insert(i);
+            }
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use crate::{
+    block::Block,
+    sim::{
+        Input,
+        Level,
+        SimError,
+        State,
+        Status,
+        TICKS_PER_SECOND,
+    },
+};
+use std::collections::{
+    HashSet,
+    VecDeque,
+};
+
+/// Options for searching for a solution
+#[derive(Debug, Clone)]
+pub struct SolverOptions {
+    /// The most states to visit before giving up
+    pub max_states: usize,
+    /// The longest solution to look for, in ticks
+    pub max_ticks: u32,
+    /// The number of ticks every choice of input is held for. Smaller is more precise, but slower.
+    pub action_ticks: u32,
+    /// Count reaching a SecretExit as a solution
+    pub secret_exit: bool,
+}
+
+impl SolverOptions {
+    /// Default SolverOptions.
+    pub fn new() -> Self {
+        Self {
+            max_states: 200_000,
+            max_ticks: 120 * TICKS_PER_SECOND,
+            action_ticks: 8,
+            secret_exit: false,
+        }
+    }
+
+    /// The most states to visit before giving up
+    pub fn max_states(mut self, max_states: usize) -> Self {
+        self.max_states = max_states;
+        self
+    }
+
+    /// The longest solution to look for, in ticks
+    pub fn max_ticks(mut self, max_ticks: u32) -> Self {
+        self.max_ticks = max_ticks;
+        self
+    }
+
+    /// The number of ticks every choice of input is held for
+    pub fn action_ticks(mut self, action_ticks: u32) -> Self {
+        self.action_ticks = action_ticks;
+        self
+    }
+
+    /// Count reaching a SecretExit as a solution
+    pub fn secret_exit(mut self, secret_exit: bool) -> Self {
+        self.secret_exit = secret_exit;
+        self
+    }
+}
+
+impl Default for SolverOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A way to beat a level
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution {
+    /// The input of every tick, ending on the tick the player reaches the exit
+    pub inputs: Vec<Input>,
+    /// The state the solution ends in
+    pub end: State,
+}
+
+impl Solution {
+    /// The length of the solution in ticks
+    pub fn ticks(&self) -> u32 {
+        self.inputs.len() as u32
+    }
+}
+
+/// What a search found
+#[derive(Debug, Clone, PartialEq)]
+pub enum SolveResult {
+    /// The solution with the fewest ticks, at the precision of action_ticks
+    Solved(Box<Solution>),
+    /// Every state reachable within max_ticks was visited without reaching an exit.
+    ///
+    /// Inputs only change every action_ticks ticks, so this is a proof for that precision.
+    Unsolvable { states: usize },
+    /// max_states states were visited without reaching an exit
+    GaveUp { states: usize },
+}
+
+impl SolveResult {
+    /// Get the solution, if one was found
+    pub fn solution(&self) -> Option<&Solution> {
+        match self {
+            Self::Solved(solution) => Some(solution.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// The choices of input tried from every state
+fn actions(state: &State) -> Vec<Input> {
+    let mut actions = vec![
+        Input::none(),
+        Input::left(),
+        Input::right(),
+        Input::jump(),
+        Input::left().with_jump(),
+        Input::right().with_jump(),
+    ];
+    if state.burrows > 0 {
+        actions.push(Input::burrow());
+    }
+    if state.recalls > 0 {
+        actions.push(Input::recall());
+    }
+
+    actions
+}
+
+/// A visited state and how it was reached
+struct Node {
+    parent: Option<usize>,
+    /// The inputs from the parent to this state
+    inputs: Vec<Input>,
+}
+
+/// Search for the solution of a level with the fewest ticks.
+///
+/// The search is breadth first over states, trying every input for action_ticks ticks from every state.
+/// Burrow and recall are only pressed for the first tick of their action.
+/// Every action but the last takes action_ticks ticks, so the shortest solution is among the first depth that reaches an exit.
+pub fn solve(blocks: &[Block], options: &SolverOptions) -> Result<SolveResult, SimError> {
+    let level = Level::new(blocks)?;
+    let action_ticks = options.action_ticks.max(1);
+    let is_goal = |status: Status| match status {
+        Status::Exited => true,
+        Status::SecretExited => options.secret_exit,
+        Status::Playing => false,
+    };
+
+    let start = level.initial_state();
+    let mut nodes = vec![Node {
+        parent: None,
+        inputs: Vec::new(),
+    }];
+    let mut visited = HashSet::new();
+    visited.insert(visit_key(&start));
+    let mut queue = VecDeque::new();
+    queue.push_back((0, start));
+
+    // The exit reached in the fewest ticks, with the node and tick it was reached from
+    let mut best: Option<(usize, u32, Vec<Input>, State)> = None;
+    while let Some((index, state)) = queue.pop_front() {
+        // Every later node is a whole action further from the start
+        if let Some((_, tick, ..)) = &best {
+            if state.tick > *tick {
+                break;
+            }
+        }
+
+        for action in actions(&state) {
+            let mut next = state.clone();
+            let mut inputs = Vec::with_capacity(action_ticks as usize);
+            for tick in 0..action_ticks {
+                let input = if tick == 0 {
+                    action
+                } else {
+                    Input {
+                        burrow: false,
+                        recall: false,
+                        ..action
+                    }
+                };
+                next = level.step(&next, input);
+                inputs.push(input);
+                if next.status.is_finished() {
+                    break;
+                }
+            }
+
+            if is_goal(next.status) {
+                if best.as_ref().is_none_or(|(.., end)| next.tick < end.tick) {
+                    best = Some((index, state.tick, inputs, next));
+                }
+                continue;
+            }
+
+            // Other exits end the level without beating it, and nothing past a solution is needed
+            if best.is_some() || next.status.is_finished() || next.tick > options.max_ticks {
+                continue;
+            }
+            if !visited.insert(visit_key(&next)) {
+                continue;
+            }
+            if nodes.len() >= options.max_states {
+                return Ok(SolveResult::GaveUp {
+                    states: nodes.len(),
+                });
+            }
+
+            nodes.push(Node {
+                parent: Some(index),
+                inputs,
+            });
+            queue.push_back((nodes.len() - 1, next));
+        }
+    }
+
+    if let Some((index, _, last, end)) = best {
+        let mut steps = vec![last.as_slice()];
+        let mut parent = Some(index);
+        while let Some(i) = parent {
+            steps.push(&nodes[i].inputs);
+            parent = nodes[i].parent;
+        }
+        let inputs = steps.into_iter().rev().flatten().copied().collect();
+        return Ok(SolveResult::Solved(Box::new(Solution { inputs, end })));
+    }
+
+    Ok(SolveResult::Unsolvable {
+        states: nodes.len(),
+    })
+}
+
+/// States that only differ in time are the same for the search
+fn visit_key(state: &State) -> State {
+    State {
+        tick: 0,
+        ..state.clone()
+    }
+}
@@ -0,0 +1,72 @@
+use sks::{
+    circuit::Circuits,
+    grid::GridError,
+    Block,
+};
+
+fn index(x: usize, y: usize) -> usize {
+    y * sks::LEVEL_WIDTH + x
+}
+
+/// A switch wired to a toggle block, a ceiling switch touching one, and an unwired switch and toggle block
+fn level() -> Vec<Block> {
+    let mut blocks = vec![Block::Empty; sks::LEVEL_SIZE];
+    blocks[index(0, 0)] = Block::Switch;
+    blocks[index(1, 0)] = Block::Wire;
+    blocks[index(2, 0)] = Block::Wire;
+    blocks[index(3, 0)] = Block::ToggleBlock { solid: true };
+    blocks[index(0, 2)] = Block::SwitchCeiling;
+    blocks[index(1, 2)] = Block::ToggleBlock { solid: false };
+    blocks[index(10, 5)] = Block::Switch;
+    blocks[index(20, 10)] = Block::ToggleBlock { solid: true };
+    blocks
+}
+
+#[test]
+fn circuit_analyze() {
+    let circuits = Circuits::analyze(&level()).unwrap();
+    assert_eq!(circuits.circuits().len(), 3);
+    assert_eq!(
+        circuits.circuits()[0].cells,
+        vec![index(0, 0), index(1, 0), index(2, 0), index(3, 0)]
+    );
+
+    assert_eq!(circuits.affected(index(0, 0)), &[index(3, 0)]);
+    assert_eq!(circuits.affected(index(0, 2)), &[index(1, 2)]);
+    // Unwired switches flip unwired toggle blocks
+    assert_eq!(circuits.affected(index(10, 5)), &[index(20, 10)]);
+    assert!(circuits.affected(index(1, 0)).is_empty());
+    assert!(circuits.affected(index(3, 0)).is_empty());
+
+    assert_eq!(circuits.circuit_of(index(20, 10)), Some(2));
+    assert_eq!(circuits.circuit_of(index(1, 0)), None);
+
+    assert_eq!(
+        Circuits::analyze(&[Block::Switch]),
+        Err(GridError::InvalidLength(1))
+    );
+}
+
+#[test]
+fn circuit_flip() {
+    let blocks = level();
+    let mut circuits = Circuits::analyze(&blocks).unwrap();
+    assert_eq!(circuits.blocks(), blocks);
+
+    assert!(circuits.activate(index(0, 0)));
+    assert!(circuits.activate(index(0, 2)));
+    assert!(!circuits.activate(index(5, 5)));
+    let flipped = circuits.blocks();
+    assert_eq!(flipped[index(3, 0)], Block::ToggleBlock { solid: false });
+    assert_eq!(flipped[index(1, 2)], Block::ToggleBlock { solid: true });
+    assert_eq!(flipped[index(20, 10)], blocks[index(20, 10)]);
+
+    // Only whether the number of activations is odd matters
+    assert!(circuits.activate_n(index(0, 0), 4));
+    assert!(circuits.is_flipped(0));
+    assert!(circuits.activate_n(index(0, 0), 3));
+    assert!(!circuits.is_flipped(0));
+
+    circuits.reset();
+    assert_eq!(circuits.blocks(), blocks);
+}
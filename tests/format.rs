@@ -71,3 +71,169 @@ fn round_kitchen_sink_as3() {
     assert_eq!(decoded1.len(), sks::LEVEL_SIZE);
     assert_eq!(decoded1, decoded); //Check that data content remains the same
 }
+
+#[test]
+fn screenshot_round_trip() {
+    let (_, data) = sks::format::decode(include_str!("levels/1-4.lbl.txt")).unwrap();
+    let expected: Vec<_> = data
+        .iter()
+        .map(|block| match block {
+            sks::Block::Note { .. } => sks::Block::Note {
+                text: String::new(),
+            },
+            block => block.clone(),
+        })
+        .collect();
+    let renderer = sks::render::ImageRenderer::new();
+
+    let opts = sks::render::RenderOptions::new().cell_size(8);
+    let img = renderer.render(&data, &opts).unwrap();
+    let screenshot = sks::format::screenshot::decode(&img).unwrap();
+    assert_eq!(screenshot.blocks, expected);
+    assert!(screenshot.confidence.iter().all(|c| *c > 0.99));
+
+    assert_eq!(screenshot.background, sks::block::BackgroundType::Cobble);
+
+    // Letterboxed on either side and not a whole number of pixels per cell
+    for (width, height) in [(300, 200), (400, 150)].iter() {
+        let opts = sks::render::RenderOptions::new()
+            .width(*width)
+            .height(*height)
+            .fit(sks::render::FitMode::Letterbox);
+        let img = renderer.render(&data, &opts).unwrap();
+        let screenshot = sks::format::screenshot::decode(&img).unwrap();
+        let layout = opts.layout();
+        assert!(layout.x > 0 || layout.y > 0);
+        assert_eq!(
+            screenshot.grid,
+            sks::format::screenshot::GridRect {
+                x: layout.x,
+                y: layout.y,
+                width: layout.content_width,
+                height: layout.content_height,
+            }
+        );
+        let matching = screenshot
+            .blocks
+            .iter()
+            .zip(expected.iter())
+            .filter(|(a, b)| a == b)
+            .count();
+        assert!(matching > sks::LEVEL_SIZE * 95 / 100, "{}", matching);
+    }
+
+    // The background comes back separately from the blocks
+    let mut pack = sks::render::TexturePack::new();
+    let blue = image::RgbaImage::from_pixel(16, 9, image::Rgba([0, 0, 255, 255]));
+    pack.insert("M1", image::DynamicImage::ImageRgba8(blue))
+        .unwrap();
+    let renderer = sks::render::ImageRenderer::with_textures(pack);
+    let mut waterfall = expected.clone();
+    let cell = waterfall.iter().position(|b| b.is_empty()).unwrap();
+    waterfall[cell] = sks::Block::Background {
+        background_type: sks::block::BackgroundType::Waterfall,
+    };
+    let img = renderer.render(&waterfall, &opts).unwrap();
+    let screenshot = sks::format::screenshot::decode_with(&renderer, &img).unwrap();
+    assert_eq!(screenshot.background, sks::block::BackgroundType::Waterfall);
+    assert_eq!(screenshot.blocks, expected);
+
+    // Blocks that look the same can't be told apart
+    let mut pack = sks::render::TexturePack::new();
+    let red = image::RgbaImage::from_pixel(16, 16, image::Rgba([255, 0, 0, 255]));
+    for name in ["B0", "E0"].iter() {
+        pack.insert(*name, image::DynamicImage::ImageRgba8(red.clone()))
+            .unwrap();
+    }
+    let renderer = sks::render::ImageRenderer::with_textures(pack);
+    let img = renderer.render(&data, &opts).unwrap();
+    let screenshot = sks::format::screenshot::decode_with(&renderer, &img).unwrap();
+    let block = data.iter().position(|b| *b == sks::Block::Block).unwrap();
+    assert_eq!(screenshot.confidence[block], 0.0);
+}
+
+#[test]
+fn replay_round_trip() {
+    use sks::{
+        format::replay::{
+            self,
+            Replay,
+            ReplayError,
+        },
+        sim::{
+            Input,
+            Status,
+        },
+        Block,
+    };
+
+    // Walk right along a floor to an exit
+    let mut blocks = vec![Block::Empty; sks::LEVEL_SIZE];
+    for block in blocks[17 * sks::LEVEL_WIDTH..].iter_mut() {
+        *block = Block::Block;
+    }
+    blocks[16 * sks::LEVEL_WIDTH] = Block::Player;
+    blocks[16 * sks::LEVEL_WIDTH + 7] = Block::Exit;
+
+    let inputs: Vec<_> = std::iter::repeat_n(Input::right().with_jump(), 10)
+        .chain(std::iter::repeat(Input::right()))
+        .take(600)
+        .collect();
+    let run = Replay::record(&blocks, &inputs).unwrap();
+    assert_eq!(run.finish_status, Status::Exited);
+    assert_eq!(run.inputs.len(), run.finish_tick as usize);
+
+    let text = replay::encode(&run);
+    assert!(text.starts_with(replay::HEADER));
+    assert!(text.contains("\n10 RJ\n"));
+    let decoded = replay::decode(&text).unwrap();
+    assert_eq!(decoded, run);
+    assert_eq!(decoded.verify(&blocks).unwrap().tick, run.finish_tick);
+
+    // Claiming a different finish
+    let mut early = decoded.clone();
+    early.finish_tick -= 1;
+    assert!(matches!(
+        early.verify(&blocks),
+        Err(ReplayError::WrongFinish { .. })
+    ));
+
+    // Stopping short of the exit
+    let mut short = decoded.clone();
+    short.inputs.pop();
+    assert_eq!(short.verify(&blocks), Err(ReplayError::NotFinished));
+
+    // Other levels and simulation versions
+    let mut edited = blocks.clone();
+    edited[0] = Block::Block;
+    assert!(matches!(
+        decoded.verify(&edited),
+        Err(ReplayError::LevelHash { .. })
+    ));
+    let mut old = decoded;
+    old.sim_version = 0;
+    assert!(matches!(
+        old.verify(&blocks),
+        Err(ReplayError::SimVersion { .. })
+    ));
+
+    assert_eq!(
+        Replay::record(&blocks, &inputs[..10]),
+        Err(ReplayError::NotFinished)
+    );
+    assert_eq!(
+        replay::decode("nope"),
+        Err(replay::DecodeError::MissingHeader)
+    );
+    let corrupt = text.replace("10 RJ", "10 RX");
+    assert!(matches!(
+        replay::decode(&corrupt),
+        Err(replay::DecodeError::InvalidLine(5))
+    ));
+    // Counts past the finish are rejected without overflowing
+    let huge = text.replace("10 RJ", &format!("{} RJ", usize::MAX));
+    assert!(matches!(
+        replay::decode(&huge),
+        Err(replay::DecodeError::InvalidLine(5))
+    ));
+}
@@ -0,0 +1,99 @@
+use sks::{
+    block::BackgroundType,
+    generate::{
+        generate,
+        GenerateError,
+        GeneratorOptions,
+        Mechanic,
+    },
+    lint::{
+        lint,
+        LintOptions,
+    },
+    Block,
+};
+
+#[test]
+fn generate_reproducible() {
+    let options = GeneratorOptions::new()
+        .seed(3)
+        .allowed(Mechanic::Key.blocks())
+        .background(BackgroundType::Waterfall);
+    let first = generate(&options).unwrap();
+    let second = generate(&options).unwrap();
+    assert_eq!(first, second);
+
+    let other = generate(&options.clone().seed(5)).unwrap();
+    assert_ne!(first.blocks, other.blocks);
+}
+
+#[test]
+fn generate_valid() {
+    let options = GeneratorOptions::new()
+        .seed(3)
+        .allowed(Mechanic::Switch.blocks())
+        .require(Mechanic::Switch)
+        .background(BackgroundType::Concrete);
+    let generated = generate(&options).unwrap();
+    let blocks = &generated.blocks;
+
+    assert_eq!(blocks.len(), sks::LEVEL_SIZE);
+    assert!(lint(blocks, &LintOptions::new()).unwrap().is_empty());
+    // Scoring a level solves it
+    assert!(generated.difficulty.features.ticks > 0);
+    assert!(blocks.contains(&Block::Background {
+        background_type: BackgroundType::Concrete
+    }));
+    // Only allowed blocks are placed
+    assert!(blocks.iter().all(|block| matches!(
+        block,
+        Block::Block
+            | Block::Empty
+            | Block::Player
+            | Block::Exit
+            | Block::Background { .. }
+            | Block::Switch
+            | Block::ToggleBlock { .. }
+    )));
+}
+
+#[test]
+fn generate_required() {
+    for mechanic in [Mechanic::Key, Mechanic::Burrow, Mechanic::Recall].iter() {
+        let options = GeneratorOptions::new()
+            .seed(3)
+            .allowed(mechanic.blocks())
+            .require(*mechanic);
+        let generated = generate(&options).unwrap();
+        let features = &generated.difficulty.features;
+        assert!(generated.mechanics.contains(mechanic));
+        match mechanic {
+            Mechanic::Key => assert!(features.locks > 0),
+            Mechanic::Burrow => assert_eq!(features.burrows, 1),
+            _ => assert_eq!(features.recalls, 1),
+        }
+    }
+
+    let options = GeneratorOptions::new()
+        .allowed(Vec::new())
+        .require(Mechanic::Pipe);
+    assert_eq!(
+        generate(&options),
+        Err(GenerateError::NotAllowed(Mechanic::Pipe))
+    );
+}
+
+#[test]
+fn generate_target_difficulty() {
+    let options = GeneratorOptions::new()
+        .seed(5)
+        .allowed(
+            [Mechanic::Key, Mechanic::Burrow]
+                .iter()
+                .flat_map(|m| m.blocks())
+                .collect(),
+        )
+        .target_difficulty(20.0, 4.0);
+    let generated = generate(&options).unwrap();
+    assert!((generated.difficulty.score - 20.0).abs() <= 4.0);
+}
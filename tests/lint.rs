@@ -0,0 +1,218 @@
+use sks::{
+    block::BackgroundType,
+    lint::{
+        apply_fixes,
+        lint,
+        Diagnostic,
+        Edit,
+        Fix,
+        LintError,
+        LintOptions,
+        Rule,
+        Severity,
+    },
+    render::{
+        ImageRenderer,
+        RenderOptions,
+        TexturePack,
+    },
+    Block,
+};
+
+/// A level with a player at (0, 0) and an exit at (1, 0)
+fn level() -> Vec<Block> {
+    let mut blocks = vec![Block::Empty; sks::LEVEL_SIZE];
+    blocks[0] = Block::Player;
+    blocks[1] = Block::Exit;
+    blocks
+}
+
+fn rules(blocks: &[Block]) -> Vec<Rule> {
+    lint(blocks, &LintOptions::new())
+        .unwrap()
+        .iter()
+        .map(|diagnostic| diagnostic.rule)
+        .collect()
+}
+
+#[test]
+fn lint_clean() {
+    assert!(rules(&level()).is_empty());
+    assert_eq!(
+        lint(&[], &LintOptions::new()).unwrap_err(),
+        LintError::InvalidLength(0)
+    );
+}
+
+#[test]
+fn lint_rules() {
+    let blocks = vec![Block::Empty; sks::LEVEL_SIZE];
+    assert_eq!(rules(&blocks), vec![Rule::SinglePlayer, Rule::MissingExit]);
+
+    let mut blocks = level();
+    blocks[40] = Block::Player;
+    let diagnostics = lint(&blocks, &LintOptions::new()).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].cells, vec![(8, 1)]);
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "error[single-player]: the level has 2 players (at 8,1)"
+    );
+
+    let mut blocks = level();
+    blocks[2] = Block::Lock;
+    blocks[3] = Block::Lock;
+    blocks[4] = Block::Key;
+    assert_eq!(rules(&blocks), vec![Rule::LockWithoutKey]);
+    blocks[5] = Block::Key;
+    assert!(rules(&blocks).is_empty());
+
+    let mut blocks = level();
+    let background = |background_type| Block::Background { background_type };
+    blocks[2] = background(BackgroundType::Waterfall);
+    assert!(rules(&blocks).is_empty());
+    blocks[3] = background(BackgroundType::Concrete);
+    assert_eq!(rules(&blocks), vec![Rule::MultipleBackgrounds]);
+    // The last background is the one used, so the earlier ones are reported
+    let diagnostics = lint(&blocks, &LintOptions::new()).unwrap();
+    assert_eq!(diagnostics[0].cells, vec![(2, 0)]);
+    assert!(diagnostics[0].message.contains("only the last is used"));
+
+    let mut blocks = level();
+    blocks[2] = Block::ToggleBlock { solid: true };
+    assert_eq!(rules(&blocks), vec![Rule::ToggleWithoutSwitch]);
+    blocks[3] = Block::SwitchCeiling;
+    assert!(rules(&blocks).is_empty());
+
+    let mut blocks = level();
+    blocks[2] = Block::Note {
+        text: " \n".to_string(),
+    };
+    blocks[3] = Block::Note {
+        text: "Hello".to_string(),
+    };
+    let diagnostics = lint(&blocks, &LintOptions::new()).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].rule, Rule::EmptyNote);
+    assert_eq!(diagnostics[0].cells, vec![(2, 0)]);
+}
+
+#[test]
+fn lint_pipes() {
+    let mut blocks = level();
+    // An entrance with no exit through a dead end, and an exit with no entrance
+    blocks[100] = Block::PipeIn;
+    blocks[101] = Block::PipeSolid;
+    blocks[200] = Block::PipeOut;
+    let diagnostics = lint(&blocks, &LintOptions::new()).unwrap();
+    let cells: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| (diagnostic.rule, diagnostic.cells.clone()))
+        .collect();
+    assert_eq!(
+        cells,
+        vec![
+            (Rule::UnpairedPipe, vec![(4, 3)]),
+            (Rule::UnpairedPipe, vec![(8, 6)]),
+            (Rule::DeadEndPipe, vec![(5, 3)]),
+        ]
+    );
+
+    blocks[102] = Block::PipePhase;
+    blocks[103] = Block::PipeOut;
+    blocks[232] = Block::PipeIn;
+    assert!(rules(&blocks).is_empty());
+}
+
+#[test]
+fn lint_options() {
+    let blocks = vec![Block::Empty; sks::LEVEL_SIZE];
+    let options = LintOptions::new()
+        .disable(Rule::MissingExit)
+        .severity(Rule::SinglePlayer, Severity::Warning);
+    let diagnostics = lint(&blocks, &options).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].rule, Rule::SinglePlayer);
+    assert_eq!(diagnostics[0].severity, Severity::Warning);
+
+    let options = options.enable(Rule::MissingExit);
+    assert_eq!(lint(&blocks, &options).unwrap().len(), 2);
+
+    for rule in Rule::ALL.iter() {
+        assert_eq!(Rule::from_name(rule.name()), Some(*rule));
+    }
+    assert_eq!(Rule::from_name("nope"), None);
+}
+
+#[test]
+fn lint_fixes() {
+    let note = |text: &str| Block::Note {
+        text: text.to_string(),
+    };
+    let mut blocks = level();
+    blocks[2] = Block::Background {
+        background_type: BackgroundType::Waterfall,
+    };
+    blocks[3] = Block::Background {
+        background_type: BackgroundType::Concrete,
+    };
+    blocks[4] = Block::Player;
+    blocks[5] = note("Hello \nthere\t\n\n");
+    blocks[6] = note("  ");
+    blocks[7] = Block::Lock;
+
+    let diagnostics = lint(&blocks, &LintOptions::new()).unwrap();
+    let fixed = apply_fixes(&blocks, &diagnostics);
+    assert_eq!(fixed[2], Block::Empty);
+    assert_eq!(fixed[3], blocks[3]);
+    assert_eq!(fixed[4], Block::Empty);
+    assert_eq!(fixed[5], note("Hello\nthere"));
+    assert_eq!(fixed[6], Block::Empty);
+
+    // Removing backgrounds doesn't change the one the level is drawn with
+    let mut pack = TexturePack::new();
+    for (name, color) in [("M1", [0, 0, 255, 255]), ("M3", [0, 255, 0, 255])].iter() {
+        let img = image::RgbaImage::from_pixel(16, 9, image::Rgba(*color));
+        pack.insert(*name, image::DynamicImage::ImageRgba8(img))
+            .unwrap();
+    }
+    let renderer = ImageRenderer::with_textures(pack);
+    let opts = RenderOptions::new().cell_size(4);
+    let background_color = |blocks: &[Block]| {
+        let img = renderer.render(blocks, &opts).unwrap().into_rgba8();
+        img.get_pixel(20 * 4, 10 * 4).0
+    };
+    assert_eq!(background_color(&blocks), [0, 255, 0, 255]);
+    assert_eq!(background_color(&fixed), background_color(&blocks));
+
+    // Problems without a mechanical fix are left
+    assert_eq!(rules(&fixed), vec![Rule::LockWithoutKey]);
+    let fix = diagnostics
+        .iter()
+        .find(|diagnostic| diagnostic.rule == Rule::SinglePlayer)
+        .and_then(|diagnostic| diagnostic.fix.as_ref())
+        .unwrap();
+    assert_eq!(fix.edits.len(), 1);
+    assert_eq!(fix.edits[0].cell, (4, 0));
+
+    // Fixes that touch the same cell as an earlier fix are skipped whole
+    let edit = |cell, block| Edit { cell, block };
+    let diagnostic = |edits| Diagnostic {
+        rule: Rule::SinglePlayer,
+        severity: Severity::Error,
+        message: String::new(),
+        cells: Vec::new(),
+        fix: Some(Fix {
+            description: String::new(),
+            edits,
+        }),
+    };
+    let conflicting = vec![
+        diagnostic(vec![edit((4, 0), Block::Block)]),
+        diagnostic(vec![edit((5, 0), Block::Key), edit((4, 0), Block::Lock)]),
+    ];
+    let fixed = apply_fixes(&blocks, &conflicting);
+    assert_eq!(fixed[4], Block::Block);
+    assert_eq!(fixed[5], blocks[5]);
+}
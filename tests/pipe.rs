@@ -0,0 +1,94 @@
+use sks::{
+    grid::GridError,
+    pipe::{
+        PipeProblem,
+        PipeProblemKind,
+        Pipes,
+    },
+    Block,
+};
+
+fn index(x: usize, y: usize) -> usize {
+    y * sks::LEVEL_WIDTH + x
+}
+
+#[test]
+fn pipe_pairs() {
+    let mut blocks = vec![Block::Empty; sks::LEVEL_SIZE];
+    // One entrance in the middle of a pipe with an exit at either end, closer to the right one
+    blocks[index(0, 5)] = Block::PipeOut;
+    for x in 1..8 {
+        blocks[index(x, 5)] = Block::PipeSolid;
+    }
+    blocks[index(3, 5)] = Block::PipePhase;
+    blocks[index(4, 5)] = Block::PipePhase;
+    blocks[index(6, 5)] = Block::PipePhase;
+    blocks[index(5, 4)] = Block::PipeIn;
+    blocks[index(8, 5)] = Block::PipeOut;
+
+    let pipes = Pipes::analyze(&blocks).unwrap();
+    assert_eq!(pipes.networks.len(), 1);
+    let network = &pipes.networks[0];
+    assert_eq!(network.cells.len(), 10);
+    assert_eq!(network.entrances, vec![index(5, 4)]);
+    assert_eq!(network.exits, vec![index(0, 5), index(8, 5)]);
+    assert_eq!(
+        network.phases,
+        vec![vec![index(3, 5), index(4, 5)], vec![index(6, 5)]]
+    );
+    assert_eq!(pipes.exit_for(index(5, 4)), Some(index(8, 5)));
+    assert_eq!(pipes.exit_for(index(8, 5)), None);
+    // Exits no entrance leads to are reported, even if their network has entrances
+    assert_eq!(
+        pipes.problems,
+        vec![PipeProblem {
+            kind: PipeProblemKind::ExitWithoutEntrance,
+            cell: (0, 5),
+            network: 0,
+        }]
+    );
+
+    // Ties go to the first exit
+    blocks[index(4, 4)] = Block::PipeIn;
+    blocks[index(5, 4)] = Block::Empty;
+    let pipes = Pipes::analyze(&blocks).unwrap();
+    assert_eq!(pipes.exit_for(index(4, 4)), Some(index(0, 5)));
+}
+
+#[test]
+fn pipe_problems() {
+    let mut blocks = vec![Block::Empty; sks::LEVEL_SIZE];
+    blocks[index(2, 2)] = Block::PipeIn;
+    blocks[index(3, 2)] = Block::PipeSolid;
+    blocks[index(4, 2)] = Block::PipePhase;
+    blocks[index(10, 10)] = Block::PipeOut;
+
+    let pipes = Pipes::analyze(&blocks).unwrap();
+    assert_eq!(pipes.networks.len(), 2);
+    assert_eq!(pipes.networks[0].pairs, vec![(index(2, 2), None)]);
+    assert_eq!(
+        pipes.problems,
+        vec![
+            PipeProblem {
+                kind: PipeProblemKind::EntranceWithoutExit,
+                cell: (2, 2),
+                network: 0,
+            },
+            PipeProblem {
+                kind: PipeProblemKind::DeadEnd,
+                cell: (4, 2),
+                network: 0,
+            },
+            PipeProblem {
+                kind: PipeProblemKind::ExitWithoutEntrance,
+                cell: (10, 10),
+                network: 1,
+            },
+        ]
+    );
+
+    assert_eq!(
+        Pipes::analyze(&blocks[1..]),
+        Err(GridError::InvalidLength(sks::LEVEL_SIZE - 1))
+    );
+}
@@ -1,6 +1,37 @@
-use sks::render::{
-    ImageRenderer,
-    RenderOptions,
+use image::GenericImageView;
+use sks::{
+    block::BackgroundType,
+    format::as3::LevelNum,
+    render::{
+        Animation,
+        AnimationFormat,
+        AnimationOptions,
+        CellChange,
+        CellRect,
+        ColorMode,
+        ColorRamp,
+        Connectivity,
+        DiffLayout,
+        DiffOptions,
+        EncodeFormat,
+        FitMode,
+        HeatmapOptions,
+        ImageRenderer,
+        ImageRequest,
+        Overlay,
+        Path,
+        Point,
+        RenderError,
+        RenderOptions,
+        ScaleFilter,
+        SheetEntry,
+        SheetLayout,
+        SheetOptions,
+        TextMode,
+        TextRenderOptions,
+        TextRenderer,
+    },
+    Block,
 };
 use std::time::Instant;
 
@@ -9,11 +40,8 @@ fn image_renderer_cache() {
     let lvl = include_str!("levels/1-4.lbl.txt");
     let (_, data) = sks::format::decode(lvl).unwrap();
 
-    let mut renderer = ImageRenderer::new();
-    let opts = RenderOptions {
-        width: 1280,
-        height: 720,
-    };
+    let renderer = ImageRenderer::new();
+    let opts = RenderOptions::new().width(1280).height(720);
     let start = Instant::now();
 
     #[allow(unused_variables)]
@@ -41,4 +69,573 @@ fn image_renderer_cache() {
     assert!(time1 > time2);
 }
 
+#[test]
+fn image_renderer_fit() {
+    let lvl = include_str!("levels/1-4.lbl.txt");
+    let (_, data) = sks::format::decode(lvl).unwrap();
+    let renderer = ImageRenderer::new();
+
+    let opts = RenderOptions::new().width(1000).height(600);
+    let img = renderer.render(&data, &opts).unwrap();
+    assert_eq!(img.dimensions(), (1000, 600));
+
+    let opts = opts.fit(FitMode::Contain);
+    let img = renderer.render(&data, &opts).unwrap();
+    assert_eq!(img.dimensions(), (1000, 562));
+
+    let opts = opts
+        .fit(FitMode::Letterbox)
+        .letterbox_color([255, 0, 255, 255]);
+    let img = renderer.render(&data, &opts).unwrap();
+    assert_eq!(img.dimensions(), (1000, 600));
+    assert_eq!(img.get_pixel(0, 0).0, [255, 0, 255, 255]);
+    assert_ne!(img.get_pixel(500, 300).0, [255, 0, 255, 255]);
+}
+
+#[test]
+fn image_renderer_integer_scale() {
+    let lvl = include_str!("levels/1-4.lbl.txt");
+    let (_, data) = sks::format::decode(lvl).unwrap();
+    let renderer = ImageRenderer::new();
+
+    let opts = RenderOptions::new().integer_scale(10);
+    let img = renderer.render(&data, &opts).unwrap();
+    assert_eq!(img.dimensions(), (320, 180));
+
+    let layout = RenderOptions::new()
+        .width(1000)
+        .height(600)
+        .fit(FitMode::Integer)
+        .layout();
+    assert_eq!((layout.content_width, layout.content_height), (992, 558));
+    assert_eq!((layout.x, layout.y), (4, 21));
+    for x in 0..sks::LEVEL_WIDTH as u32 {
+        assert_eq!(layout.cell_rect(x, 0).2, 31);
+    }
+
+    // Textures that aren't square keep their aspect ratio instead of filling the cell
+    let request = ImageRequest {
+        w: 20,
+        h: 20,
+        block: Block::Note {
+            text: String::new(),
+        },
+        filter: ScaleFilter::Nearest,
+    };
+    let (width, height) = ImageRenderer::generate_block_image(&request)
+        .unwrap()
+        .dimensions();
+    assert!(width < 20);
+    assert_eq!(height, 20);
+}
+
+#[test]
+fn image_renderer_cache_bounded() {
+    let lvl = include_str!("levels/1-4.lbl.txt");
+    let (_, data) = sks::format::decode(lvl).unwrap();
+    let renderer = ImageRenderer::new().cache_capacity(40_000);
+
+    renderer
+        .render(&data, &RenderOptions::new().integer_scale(4))
+        .unwrap();
+    let stats = renderer.cache_stats();
+    assert_eq!(stats.evictions, 0);
+    assert!(stats.hits > 0);
+
+    renderer
+        .render(&data, &RenderOptions::new().integer_scale(3))
+        .unwrap();
+    let stats = renderer.cache_stats();
+    assert!(stats.evictions > 0);
+    assert!(stats.bytes <= stats.capacity);
+
+    renderer.clear_cache();
+    let stats = renderer.cache_stats();
+    assert_eq!((stats.entries, stats.bytes), (0, 0));
+}
+
+#[test]
+fn image_renderer_shared() {
+    let lvl = include_str!("levels/1-4.lbl.txt");
+    let (_, data) = sks::format::decode(lvl).unwrap();
+    let renderer = ImageRenderer::new();
+    let opts = RenderOptions::new().cell_size(8);
+
+    let images: Vec<_> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..4)
+            .map(|_| scope.spawn(|| renderer.render(&data, &opts).unwrap()))
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    for img in images.iter() {
+        assert_eq!(img.as_bytes(), images[0].as_bytes());
+    }
+}
+
+#[test]
+fn image_renderer_batch() {
+    let lvl = include_str!("levels/1-4.lbl.txt");
+    let (_, data) = sks::format::decode(lvl).unwrap();
+    let levels = vec![data.clone(), data[1..].to_vec(), data];
+    let renderer = ImageRenderer::new();
+    let opts = RenderOptions::new().cell_size(4);
+
+    let mut items = Vec::new();
+    renderer.render_batch(&levels, &opts, |item| items.push(item));
+    assert_eq!(items.len(), levels.len());
+    for (i, item) in items.iter().enumerate() {
+        assert_eq!(item.progress.completed, i + 1);
+        assert_eq!(item.progress.total, levels.len());
+    }
+
+    items.sort_by_key(|item| item.index);
+    assert!(items[0].result.is_ok());
+    assert!(matches!(
+        items[1].result,
+        Err(RenderError::InvalidLength(len)) if len == sks::LEVEL_SIZE - 1
+    ));
+    assert!(items[2].result.is_ok());
+
+    let all = renderer.render_all(&levels, &opts);
+    assert_eq!(
+        all[0].as_ref().unwrap().as_bytes(),
+        items[0].result.as_ref().unwrap().as_bytes()
+    );
+}
+
+#[test]
+fn image_renderer_sheet() {
+    let lvl = include_str!("levels/1-4.lbl.txt");
+    let (_, data) = sks::format::decode(lvl).unwrap();
+    let renderer = ImageRenderer::new();
+
+    let entries = vec![
+        SheetEntry::new(&data).level_num(LevelNum::String("1-4".into())),
+        SheetEntry::new(&data).level_num(LevelNum::String("1-2".into())),
+        SheetEntry::new(&data).level_num(LevelNum::String("2-1".into())),
+        SheetEntry::new(&data),
+    ];
+    let opts = SheetOptions::new()
+        .thumbnail(RenderOptions::new().cell_size(2))
+        .spacing(4)
+        .border(1, [255, 0, 0, 255])
+        .captions(1, [255, 255, 255, 255]);
+
+    // Thumbnails are 64x36, slots are 66x47 with the border and caption
+    let opts = opts.layout(SheetLayout::Grid { columns: 3 });
+    let sheet = renderer.render_sheet(&entries, &opts).unwrap();
+    assert_eq!(sheet.dimensions(), (3 * 66 + 4 * 4, 2 * 47 + 3 * 4));
+    assert_eq!(sheet.get_pixel(4, 4).0, [255, 0, 0, 255]);
+
+    // 1-2 and 1-4 go in the first row, 2-1 in the second, and the level without a number after that
+    let opts = opts.layout(SheetLayout::WorldMap {
+        levels_per_world: 10,
+    });
+    let sheet = renderer.render_sheet(&entries, &opts).unwrap();
+    assert_eq!(sheet.dimensions(), (4 * 66 + 5 * 4, 3 * 47 + 4 * 4));
+
+    // Level numbers far apart don't fit on a sheet
+    for far in ["1-100000000", "18446744073709551615-1"].iter() {
+        let entries = vec![
+            SheetEntry::new(&data).level_num(LevelNum::String("1-1".into())),
+            SheetEntry::new(&data).level_num(LevelNum::String(far.to_string())),
+        ];
+        assert!(matches!(
+            renderer.render_sheet(&entries, &opts),
+            Err(RenderError::SheetTooLarge { .. })
+        ));
+    }
+    let entries = vec![
+        SheetEntry::new(&data).level_num(LevelNum::Num(0)),
+        SheetEntry::new(&data).level_num(LevelNum::Num(usize::MAX)),
+    ];
+    assert!(matches!(
+        renderer.render_sheet(&entries, &opts),
+        Err(RenderError::SheetTooLarge { .. })
+    ));
+}
+
+#[test]
+fn image_renderer_diff() {
+    let lvl = include_str!("levels/1-4.lbl.txt");
+    let (_, old) = sks::format::decode(lvl).unwrap();
+    let mut new = old.clone();
+    let empty = old.iter().position(|b| b.is_empty()).unwrap();
+    let block = old.iter().position(|b| *b == Block::Block).unwrap();
+    new[empty] = Block::Key;
+    new[block] = Block::Empty;
+
+    let changes = sks::render::diff::diff_cells(&old, &new);
+    assert_eq!(changes[empty], CellChange::Added);
+    assert_eq!(changes[block], CellChange::Removed);
+    assert_eq!(
+        changes
+            .iter()
+            .filter(|c| **c == CellChange::Unchanged)
+            .count(),
+        sks::LEVEL_SIZE - 2
+    );
+
+    let renderer = ImageRenderer::new();
+    let opts = RenderOptions::new().cell_size(8);
+    let diff_opts = DiffOptions::new().outline_width(1);
+    let img = renderer.render_diff(&old, &new, &opts, &diff_opts).unwrap();
+    assert_eq!(img.dimensions(), (256, 144));
+    let (x, y) = (empty % sks::LEVEL_WIDTH, empty / sks::LEVEL_WIDTH);
+    assert_eq!(
+        img.get_pixel(x as u32 * 8, y as u32 * 8).0,
+        diff_opts.added_color
+    );
+    let (x, y) = (block % sks::LEVEL_WIDTH, block / sks::LEVEL_WIDTH);
+    assert_eq!(
+        img.get_pixel(x as u32 * 8, y as u32 * 8).0,
+        diff_opts.removed_color
+    );
+    assert!(matches!(
+        renderer.render_diff(&old[1..], &new, &opts, &diff_opts),
+        Err(RenderError::InvalidLength(_))
+    ));
+
+    let diff_opts = diff_opts.layout(DiffLayout::SideBySide { spacing: 4 });
+    let img = renderer.render_diff(&old, &new, &opts, &diff_opts).unwrap();
+    assert_eq!(img.dimensions(), (256 * 2 + 4, 144));
+    let (x, y) = (block % sks::LEVEL_WIDTH, block / sks::LEVEL_WIDTH);
+    assert_eq!(
+        img.get_pixel(x as u32 * 8, y as u32 * 8).0,
+        diff_opts.removed_color
+    );
+}
+
+#[test]
+fn image_renderer_animation() {
+    use image::AnimationDecoder;
+
+    let lvl = include_str!("levels/1-4.lbl.txt");
+    let (_, data) = sks::format::decode(lvl).unwrap();
+    let renderer = ImageRenderer::new();
+    let opts = RenderOptions::new().cell_size(2);
+
+    let anim_opts = AnimationOptions::new().delay_ms(250);
+    let gif = renderer
+        .render_animation(&Animation::Toggle(&data), &opts, &anim_opts)
+        .unwrap();
+    let frames = image::gif::GifDecoder::new(std::io::Cursor::new(gif))
+        .unwrap()
+        .into_frames()
+        .collect_frames()
+        .unwrap();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0].delay().numer_denom_ms(), (250, 1));
+    assert_ne!(frames[0].buffer(), frames[1].buffer());
+
+    // Gif counts repeats after the first play, so playing 3 times repeats twice
+    let still = renderer.render(&data, &opts).unwrap().into_rgba8();
+    let gif_loops = |loop_count| {
+        let gif = sks::render::animation::encode_gif(
+            vec![still.clone()],
+            &AnimationOptions::new().loop_count(loop_count),
+        )
+        .unwrap();
+        let start = gif.windows(11).position(|w| w == b"NETSCAPE2.0")?;
+        Some(u16::from_le_bytes([gif[start + 13], gif[start + 14]]))
+    };
+    assert_eq!(gif_loops(0), Some(0));
+    assert_eq!(gif_loops(1), None);
+    assert_eq!(gif_loops(3), Some(2));
+    assert!(matches!(
+        sks::render::animation::encode_gif(Vec::new(), &anim_opts),
+        Err(sks::render::animation::AnimationError::NoFrames)
+    ));
+
+    let anim_opts = anim_opts.format(AnimationFormat::Apng).background_frames(4);
+    let apng = renderer
+        .render_animation(&Animation::Background(&data), &opts, &anim_opts)
+        .unwrap();
+    let count = |kind: &[u8]| apng.windows(4).filter(|w| *w == kind).count();
+    assert_eq!(count(b"acTL"), 1);
+    assert_eq!(count(b"fcTL"), 4);
+    assert_eq!(count(b"fdAT"), 3);
+
+    // The first frame is also the still image
+    let still = image::load_from_memory(&apng).unwrap();
+    assert_eq!(still.dimensions(), (64, 36));
+
+    // Waterfall isn't embedded, so it falls back to the default background
+    let mut waterfall = data.clone();
+    let background = waterfall.iter().position(|b| b.is_empty()).unwrap();
+    waterfall[background] = Block::Background {
+        background_type: BackgroundType::Waterfall,
+    };
+    let frames = renderer
+        .render_animation_frames(&Animation::Background(&waterfall), &opts, &anim_opts)
+        .unwrap();
+    assert_eq!(frames.len(), 4);
+    assert_ne!(frames[0], frames[1]);
+}
+
+#[test]
+fn image_renderer_overlay() {
+    let lvl = include_str!("levels/1-4.lbl.txt");
+    let (_, data) = sks::format::decode(lvl).unwrap();
+    let renderer = ImageRenderer::new();
+    let opts = RenderOptions::new().cell_size(16);
+
+    let path = Path::from_cells(vec![(2, 2), (10, 2)])
+        .unwrap()
+        .color([255, 0, 0, 255])
+        .line_to_labeled(Point::cell(10, 10), "burrow");
+    let overlay = Overlay::new().path(path);
+    let plain = renderer.render(&data, &opts).unwrap();
+    let img = renderer.render_overlay(&data, &opts, &overlay).unwrap();
+    assert_eq!(img.dimensions(), plain.dimensions());
+
+    // On the first segment
+    assert_eq!(img.get_pixel(6 * 16 + 8, 2 * 16 + 8).0, [255, 0, 0, 255]);
+    // Far from the path
+    assert_eq!(
+        img.get_pixel(20 * 16, 15 * 16),
+        plain.get_pixel(20 * 16, 15 * 16)
+    );
+
+    assert!(Path::from_cells(Vec::new()).is_none());
+
+    // Points far outside of the image only draw the part that is inside
+    let path = Path::new(Point::cell(2, 2))
+        .line_to(Point::new(1e9, 2.5))
+        .color([255, 0, 0, 255]);
+    let img = renderer
+        .render_overlay(&data, &opts, &Overlay::new().path(path))
+        .unwrap();
+    assert_eq!(img.get_pixel(20 * 16, 2 * 16 + 8).0, [255, 0, 0, 255]);
+}
+
+#[test]
+fn image_renderer_connectivity() {
+    let lvl = include_str!("levels/1-4.lbl.txt");
+    let (_, mut data) = sks::format::decode(lvl).unwrap();
+    for block in data.iter_mut() {
+        if !block.is_background() {
+            *block = Block::Empty;
+        }
+    }
+    let i = |x: usize, y: usize| y * sks::LEVEL_WIDTH + x;
+    data[i(2, 2)] = Block::Switch;
+    data[i(3, 2)] = Block::Wire;
+    data[i(4, 2)] = Block::Wire;
+    data[i(5, 2)] = Block::ToggleBlock { solid: true };
+    data[i(10, 10)] = Block::SwitchCeiling;
+    data[i(20, 5)] = Block::ToggleBlock { solid: false };
+    data[i(2, 12)] = Block::PipeIn;
+    data[i(3, 12)] = Block::PipeSolid;
+    data[i(4, 12)] = Block::PipeOut;
+
+    let connectivity = Connectivity::analyze(&data).unwrap();
+    assert_eq!(connectivity.wires.len(), 2);
+    assert_eq!(
+        connectivity.wires[0].cells,
+        vec![i(2, 2), i(3, 2), i(4, 2), i(5, 2)]
+    );
+    // The unwired switch flips the unwired toggle block, so they share a network
+    assert_eq!(connectivity.wires[1].cells, vec![i(20, 5), i(10, 10)]);
+    assert_eq!(connectivity.pipes.len(), 1);
+    assert_eq!(connectivity.pipes[0].entrances, vec![i(2, 12)]);
+    assert_eq!(connectivity.pipes[0].exits, vec![i(4, 12)]);
+
+    let renderer = ImageRenderer::new();
+    let opts = RenderOptions::new().cell_size(16);
+    let plain = renderer.render(&data, &opts).unwrap();
+    let img = renderer.render_connectivity(&data, &opts).unwrap();
+    assert_eq!(img.dimensions(), plain.dimensions());
+
+    // Wires are drawn in the color of their network
+    let [r, g, b] = sks::render::connectivity::network_color(0);
+    assert_eq!(img.get_pixel(3 * 16 + 8, 2 * 16 + 8).0, [r, g, b, 255]);
+    let [r, g, b] = sks::render::connectivity::network_color(1);
+    assert_eq!(img.get_pixel(10 * 16, 10 * 16).0, [r, g, b, 255]);
+    assert_eq!(img.get_pixel(20 * 16, 5 * 16).0, [r, g, b, 255]);
+    // Far from every network
+    assert_eq!(
+        img.get_pixel(20 * 16, 15 * 16),
+        plain.get_pixel(20 * 16, 15 * 16)
+    );
+}
+
+#[test]
+fn image_renderer_heatmap() {
+    let lvl = include_str!("levels/1-4.lbl.txt");
+    let (_, data) = sks::format::decode(lvl).unwrap();
+    let renderer = ImageRenderer::new();
+    let opts = RenderOptions::new().cell_size(16);
+
+    let mut values = vec![0.0; sks::LEVEL_SIZE];
+    values[2 * sks::LEVEL_WIDTH + 2] = 10.0;
+    values[2 * sks::LEVEL_WIDTH + 3] = 5.0;
+    values[2 * sks::LEVEL_WIDTH + 4] = 1.0;
+    let heatmap_options = HeatmapOptions::new()
+        .ramp(ColorRamp::two_color([0, 0, 0], [255, 0, 0]))
+        .opacity(255);
+    assert_eq!(heatmap_options.value_range(&values), (0.0, 10.0));
+
+    let plain = renderer.render(&data, &opts).unwrap();
+    let img = renderer
+        .render_heatmap(&data, &values, &opts, &heatmap_options)
+        .unwrap();
+    assert_eq!(img.dimensions(), plain.dimensions());
+    assert_eq!(img.get_pixel(2 * 16 + 8, 2 * 16 + 8).0, [255, 0, 0, 255]);
+    assert_eq!(img.get_pixel(3 * 16 + 8, 2 * 16 + 8).0, [127, 0, 0, 255]);
+    // Zero cells are skipped
+    assert_eq!(img.get_pixel(8, 8), plain.get_pixel(8, 8));
+
+    assert_eq!(ColorRamp::heat().sample(0.0), [0, 0, 255]);
+    assert_eq!(ColorRamp::heat().sample(2.0), [255, 0, 0]);
+
+    assert!(matches!(
+        renderer.render_heatmap(&data, &values[1..], &opts, &heatmap_options),
+        Err(RenderError::InvalidLength(575))
+    ));
+}
+
+#[test]
+fn image_renderer_to_bytes() {
+    let lvl = include_str!("levels/1-4.lbl.txt");
+    let (_, data) = sks::format::decode(lvl).unwrap();
+    let renderer = ImageRenderer::new();
+    let opts = RenderOptions::new().cell_size(2);
+    let level_num = LevelNum::String("1-4".into());
+
+    for format in [
+        EncodeFormat::Png { palette: false },
+        EncodeFormat::Png { palette: true },
+        EncodeFormat::Jpeg,
+        EncodeFormat::WebP,
+    ]
+    .iter()
+    {
+        let bytes = renderer
+            .render_to_bytes(&data, Some(&level_num), &opts, format, 80)
+            .unwrap();
+        if let EncodeFormat::WebP = format {
+            // WebP is lossless
+            let mut decoder = image_webp::WebPDecoder::new(std::io::Cursor::new(bytes)).unwrap();
+            assert_eq!(decoder.dimensions(), (64, 36));
+            let mut buf = vec![0; decoder.output_buffer_size().unwrap()];
+            decoder.read_image(&mut buf).unwrap();
+            let plain = renderer.render(&data, &opts).unwrap().into_rgba8();
+            assert_eq!(buf, plain.into_raw());
+            continue;
+        }
+
+        let img = image::load_from_memory(&bytes).unwrap();
+        assert_eq!(img.dimensions(), (64, 36));
+
+        let source = sks::render::encode::embedded_source(&bytes);
+        match format {
+            EncodeFormat::Png { .. } => {
+                let source = source.unwrap();
+                assert_eq!(source.blocks, data);
+                assert_eq!(source.level_num, Some(level_num.clone()));
+            }
+            _ => assert!(source.is_none()),
+        }
+    }
+}
+
+#[test]
+fn image_renderer_viewport() {
+    let lvl = include_str!("levels/1-4.lbl.txt");
+    let (_, data) = sks::format::decode(lvl).unwrap();
+    let renderer = ImageRenderer::new();
+    let full = renderer
+        .render(&data, &RenderOptions::new().cell_size(8))
+        .unwrap();
+    let misses = renderer.cache_stats().misses;
+
+    let viewport = CellRect::new(4, 2, 10, 6);
+    let opts = RenderOptions::new().viewport(viewport).cell_size(8);
+    let img = renderer.render(&data, &opts).unwrap();
+    assert_eq!(img.dimensions(), (80, 48));
+    assert_eq!(img.to_rgba8(), full.crop_imm(32, 16, 80, 48).to_rgba8());
+    // Everything needed was already cached by the full render
+    assert_eq!(renderer.cache_stats().misses, misses);
+
+    let opts = RenderOptions::new()
+        .viewport(viewport)
+        .margin(1)
+        .cell_size(8);
+    let img = renderer.render(&data, &opts).unwrap();
+    assert_eq!(img.dimensions(), (96, 64));
+    assert_eq!(
+        img.crop_imm(8, 8, 80, 48).to_rgba8(),
+        full.crop_imm(32, 16, 80, 48).to_rgba8()
+    );
+    // The margin is dimmed
+    assert_ne!(img.get_pixel(4, 4), full.get_pixel(28, 12));
+    // The cell size may be set before the viewport and margin
+    let opts = RenderOptions::new()
+        .cell_size(8)
+        .viewport(viewport)
+        .margin(1);
+    assert_eq!(renderer.render(&data, &opts).unwrap(), img);
+
+    // Clamped to the level
+    let opts = RenderOptions::new()
+        .viewport(CellRect::new(30, 16, 5, 5))
+        .margin(1)
+        .cell_size(8);
+    assert_eq!(opts.visible_cells(), CellRect::new(29, 15, 3, 3));
+
+    let opts = RenderOptions::new().viewport(CellRect::new(40, 0, 2, 2));
+    assert!(matches!(
+        renderer.render(&data, &opts),
+        Err(RenderError::EmptyViewport)
+    ));
+}
+
+#[test]
+fn text_renderer_ascii() {
+    let lvl = include_str!("levels/1-4.lbl.txt");
+    let (_, data) = sks::format::decode(lvl).unwrap();
+
+    let renderer = TextRenderer::new();
+    let text = renderer.render(&data, &TextRenderOptions::new()).unwrap();
+
+    assert!(text.is_ascii());
+    assert_eq!(text.lines().count(), sks::LEVEL_HEIGHT);
+    assert!(text.lines().all(|l| l.len() == sks::LEVEL_WIDTH));
+    assert_eq!(text.matches('@').count(), 1);
+}
+
+#[test]
+fn text_renderer_half_block() {
+    let lvl = include_str!("levels/1-4.lbl.txt");
+    let (_, data) = sks::format::decode(lvl).unwrap();
+
+    let renderer = TextRenderer::new();
+    let opts = TextRenderOptions::new()
+        .mode(TextMode::HalfBlock)
+        .color(ColorMode::TrueColor)
+        .cell_size(2);
+    let text = renderer.render(&data, &opts).unwrap();
+    assert_eq!(text.lines().count(), sks::LEVEL_HEIGHT);
+    assert_eq!(
+        text.lines().next().unwrap().matches('▀').count(),
+        sks::LEVEL_WIDTH * 2
+    );
+    // Colors are only set when they change
+    for line in text.lines() {
+        let foregrounds: Vec<&str> = line
+            .split("\x1b[38;2;")
+            .skip(1)
+            .map(|escape| &escape[..escape.find('m').unwrap()])
+            .collect();
+        assert!(foregrounds.windows(2).all(|w| w[0] != w[1]));
+    }
+
+    // No color means no half blocks
+    let opts = opts.color(ColorMode::None);
+    let text = renderer.render(&data, &opts).unwrap();
+    assert!(text.is_ascii());
+}
+
 // TODO: Consider adding tests comparing actual rendered data. This might fail after bumping the image crate.
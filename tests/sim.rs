@@ -0,0 +1,430 @@
+use sks::{
+    block::Direction,
+    sim::{
+        difficulty::{
+            self,
+            Difficulty,
+            DifficultyError,
+            DifficultyOptions,
+            DifficultyWeights,
+        },
+        reach::Reachability,
+        solver::{
+            solve,
+            SolveResult,
+            SolverOptions,
+        },
+        Input,
+        SimError,
+        Simulation,
+        Status,
+        UNITS_PER_CELL,
+    },
+    Block,
+};
+
+/// Make a level from rows of text, aligned to the bottom of the level.
+///
+/// '#' is a block, '=' is scaffold, 'X' is the player, 'E' is an exit, and 'S' is a secret exit.
+/// 'K' is a key, 'L' a lock, 's' a switch, 'c' a ceiling switch, 'W' a wire, and 'T' and 't' solid and open toggle blocks.
+/// '^', 'v', '<' and '>' are one way walls, 'I', 'O' and 'P' are pipe entrances, exits and solid pipes,
+/// and 'B' and 'R' are the burrow and recall power-ups.
+fn level(rows: &[&str]) -> Vec<Block> {
+    let mut blocks = vec![Block::Empty; sks::LEVEL_SIZE];
+    let top = sks::LEVEL_HEIGHT - rows.len();
+    for (y, row) in rows.iter().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            blocks[(top + y) * sks::LEVEL_WIDTH + x] = match c {
+                '#' => Block::Block,
+                '=' => Block::Scaffold,
+                'X' => Block::Player,
+                'E' => Block::Exit,
+                'S' => Block::SecretExit,
+                'K' => Block::Key,
+                'L' => Block::Lock,
+                's' => Block::Switch,
+                'c' => Block::SwitchCeiling,
+                'T' => Block::ToggleBlock { solid: true },
+                't' => Block::ToggleBlock { solid: false },
+                '^' => one_way(Direction::Up),
+                'v' => one_way(Direction::Down),
+                '<' => one_way(Direction::Left),
+                '>' => one_way(Direction::Right),
+                'I' => Block::PipeIn,
+                'O' => Block::PipeOut,
+                'P' => Block::PipeSolid,
+                'W' => Block::Wire,
+                'B' => Block::PowerUpBurrow,
+                'R' => Block::PowerUpRecall,
+                _ => Block::Empty,
+            };
+        }
+    }
+    blocks
+}
+
+fn one_way(direction: Direction) -> Block {
+    Block::OneWayWall { direction }
+}
+
+/// Solve a level and check the solution by replaying it
+fn assert_solvable(rows: &[&str]) {
+    let blocks = level(rows);
+    let result = solve(&blocks, &SolverOptions::new()).unwrap();
+    let solution = match result {
+        SolveResult::Solved(solution) => solution,
+        result => panic!("{:?}\n{}", result, rows.join("\n")),
+    };
+
+    let mut sim = Simulation::new(&blocks).unwrap();
+    let state = sim.run(solution.inputs.iter().copied());
+    assert_eq!(state.status, Status::Exited);
+    assert_eq!(*state, solution.end);
+    assert_eq!(state.tick, solution.ticks());
+}
+
+fn assert_unsolvable(rows: &[&str]) {
+    let result = solve(&level(rows), &SolverOptions::new()).unwrap();
+    assert!(
+        matches!(result, SolveResult::Unsolvable { .. }),
+        "{:?}\n{}",
+        result.solution().map(|solution| solution.ticks()),
+        rows.join("\n")
+    );
+}
+
+#[test]
+fn sim_walk_to_exit() {
+    let blocks = level(&["X......E", "########"]);
+    let mut sim = Simulation::new(&blocks).unwrap();
+    assert!(sim.state().player.on_ground);
+
+    let state = sim.run(std::iter::repeat_n(Input::right(), 600)).clone();
+    assert_eq!(state.status, Status::Exited);
+    assert_eq!(state.player.cell(), (7, 16));
+    // 7 cells at 24 units per tick, give or take the player's width
+    assert!(state.tick > 60 && state.tick < 80, "{}", state.tick);
+
+    // Runs are deterministic
+    let mut again = Simulation::new(&blocks).unwrap();
+    assert_eq!(*again.run(std::iter::repeat_n(Input::right(), 600)), state);
+}
+
+#[test]
+fn sim_walls_and_jumps() {
+    let blocks = level(&["X..#..E", "#######"]);
+
+    // Walking stops at the wall
+    let mut sim = Simulation::new(&blocks).unwrap();
+    let state = sim.run(std::iter::repeat_n(Input::right(), 120));
+    assert_eq!(state.status, Status::Playing);
+    assert_eq!(state.player.x, 3 * UNITS_PER_CELL - sks::sim::PLAYER_WIDTH);
+
+    // Jumping clears it
+    let mut sim = Simulation::new(&blocks).unwrap();
+    let inputs =
+        std::iter::once(Input::right().with_jump()).chain(std::iter::repeat(Input::right()));
+    let state = sim.run(inputs.take(120));
+    assert_eq!(state.status, Status::Exited);
+
+    // A three block wall is too high
+    let blocks = level(&["...#...", "...#...", "X..#..E", "#######"]);
+    let mut sim = Simulation::new(&blocks).unwrap();
+    let state = sim.run(std::iter::repeat_n(Input::right().with_jump(), 600));
+    assert_eq!(state.status, Status::Playing);
+}
+
+#[test]
+fn sim_scaffold() {
+    let blocks = level(&["S......", ".......", "===....", "X......", "#######"]);
+
+    // Jumping up through the platform, then landing on it
+    let mut sim = Simulation::new(&blocks).unwrap();
+    let inputs = std::iter::once(Input::jump()).chain(std::iter::repeat(Input::none()));
+    let state = sim.run(inputs.take(60)).clone();
+    assert!(state.player.on_ground);
+    assert_eq!(
+        state.player.y,
+        15 * UNITS_PER_CELL - sks::sim::PLAYER_HEIGHT
+    );
+
+    // The secret exit is only in reach from the platform
+    let state = sim.run(std::iter::repeat_n(Input::jump(), 60));
+    assert_eq!(state.status, Status::SecretExited);
+}
+
+#[test]
+fn sim_errors() {
+    assert_eq!(
+        Simulation::new(&[]).unwrap_err(),
+        SimError::InvalidLength(0)
+    );
+    assert_eq!(
+        Simulation::new(&level(&["...."])).unwrap_err(),
+        SimError::MissingPlayer
+    );
+}
+
+#[test]
+fn solve_basic() {
+    assert_solvable(&["X..#..E", "#######"]);
+    assert_unsolvable(&["...#...", "...#...", "X..#..E", "#######"]);
+
+    // Secret exits only count when asked for
+    let blocks = level(&["S......", ".......", "===....", "X......", "#######"]);
+    let result = solve(&blocks, &SolverOptions::new()).unwrap();
+    assert!(matches!(result, SolveResult::Unsolvable { .. }));
+    let result = solve(&blocks, &SolverOptions::new().secret_exit(true)).unwrap();
+    assert_eq!(result.solution().unwrap().end.status, Status::SecretExited);
+
+    // Running out of states isn't a proof
+    let result = solve(
+        &level(&["X..#..E", "#######"]),
+        &SolverOptions::new().max_states(10),
+    )
+    .unwrap();
+    assert!(matches!(result, SolveResult::GaveUp { states: 10 }));
+}
+
+#[test]
+fn solve_keys_and_locks() {
+    assert_solvable(&["##########", "X.K..L..E#", "##########"]);
+    assert_unsolvable(&["##########", "X....L..E#", "##########"]);
+    // The key has to be picked up before the lock
+    assert_unsolvable(&["##########", "X....L.KE#", "##########"]);
+}
+
+#[test]
+fn solve_toggles() {
+    assert_solvable(&["##########", "X.s..T..E#", "##########"]);
+    assert_solvable(&["##########", "...c..T..#", "X.....T.E#", "##########"]);
+    assert_unsolvable(&["##########", "......T..#", "X.....T.E#", "##########"]);
+    assert_solvable(&["##########", "X.t.s....E", "##########"]);
+    assert_unsolvable(&["##########", "X....T..E#", "##########"]);
+
+    // Wired switches only flip the toggle blocks of their circuit
+    let wired = ["##########", "##WWWW####", "X.s..T..E#", "##########"];
+    assert_solvable(&wired);
+    let elsewhere = ["##########", "#tWW######", "X.s..T..E#", "##########"];
+    assert_unsolvable(&elsewhere);
+}
+
+#[test]
+fn solve_one_way_walls() {
+    assert_solvable(&["##########", "X..>...E.#", "##########"]);
+    assert_unsolvable(&["##########", "X..<...E.#", "##########"]);
+    // Falling through a floor that can't be climbed back up
+    assert_solvable(&[
+        "###########",
+        "X.........#",
+        "###v#######",
+        "..........E",
+        "###########",
+    ]);
+    assert_unsolvable(&[
+        "###########",
+        ".........E#",
+        "###v#######",
+        "X..........",
+        "###########",
+    ]);
+}
+
+#[test]
+fn solve_pipes() {
+    assert_solvable(&["##########", "X.I#..O.E#", "##PPPPP###"]);
+    assert_unsolvable(&["##########", "X.I#..O.E#", "##PP.PP###"]);
+}
+
+#[test]
+fn solve_power_ups() {
+    // Burrowing through the floor
+    assert_solvable(&["########", "X.B....#", "########", "......E#", "########"]);
+    assert_unsolvable(&["########", "X......#", "########", "......E#", "########"]);
+
+    // Recalling back up after fetching a key
+    let rows = [
+        "###########",
+        "X.R....L.E#",
+        "###v#######",
+        "...K......#",
+        "###########",
+    ];
+    assert_solvable(&rows);
+    let rows = [
+        "###########",
+        "X......L.E#",
+        "###v#######",
+        "...K......#",
+        "###########",
+    ];
+    assert_unsolvable(&rows);
+}
+
+/// Check whether the analysis finds the exit
+fn assert_reaches_exit(rows: &[&str], exit: bool) -> Reachability {
+    let reach = Reachability::analyze(&level(rows)).unwrap();
+    assert_eq!(reach.exit, exit, "\n{}", rows.join("\n"));
+    reach
+}
+
+#[test]
+fn reach_basic() {
+    let reach = assert_reaches_exit(&["X..#..E", "#######"], true);
+    assert!(reach.is_reachable(0, 16));
+    assert!(reach.is_reachable(3, 13));
+    assert!(!reach.is_reachable(3, 12));
+    assert!(!reach.is_reachable(3, 16));
+    assert!(!reach.secret_exit);
+
+    assert_reaches_exit(&["...#...", "...#...", "X..#..E", "#######"], false);
+
+    let reach = assert_reaches_exit(
+        &["S......", ".......", "===....", "X......", "#######"],
+        false,
+    );
+    assert!(reach.secret_exit);
+}
+
+#[test]
+fn reach_keys_and_locks() {
+    let reach = assert_reaches_exit(&["##########", "X.K..L..E#", "##########"], true);
+    assert_eq!(reach.opened.iter().collect::<Vec<_>>(), vec![16 * 32 + 5]);
+    assert!(reach.unreachable.is_empty());
+
+    let reach = assert_reaches_exit(&["##########", "X....L..E#", "##########"], false);
+    assert!(reach.opened.is_empty());
+
+    // One key opens one lock
+    assert_reaches_exit(&["###########", "X.K..L.L.E#", "###########"], false);
+    assert_reaches_exit(&["###########", "XKK..L.L.E#", "###########"], true);
+
+    let reach = assert_reaches_exit(&["##########", "X....L.KE#", "##########"], false);
+    assert_eq!(reach.unreachable, vec![16 * 32 + 7]);
+}
+
+#[test]
+fn reach_toggles() {
+    let reach = assert_reaches_exit(&["##########", "X.s..T..E#", "##########"], true);
+    assert_eq!(
+        reach.flippable.iter().collect::<Vec<_>>(),
+        vec![16 * 32 + 5]
+    );
+    assert_reaches_exit(
+        &["##########", "...c..T..#", "X.....T.E#", "##########"],
+        true,
+    );
+    assert_reaches_exit(&["##########", "X.t.s....E", "##########"], true);
+
+    assert_reaches_exit(
+        &["##########", "##WWWW####", "X.s..T..E#", "##########"],
+        true,
+    );
+    assert_reaches_exit(
+        &["##########", "#tWW######", "X.s..T..E#", "##########"],
+        false,
+    );
+
+    let reach = assert_reaches_exit(&["##########", "X....T.sE#", "##########"], false);
+    assert!(reach.flippable.is_empty());
+    assert_eq!(reach.unreachable, vec![16 * 32 + 7]);
+}
+
+#[test]
+fn reach_one_way_walls_and_pipes() {
+    assert_reaches_exit(&["##########", "X..>...E.#", "##########"], true);
+    assert_reaches_exit(&["##########", "X..<...E.#", "##########"], false);
+    assert_reaches_exit(
+        &[
+            "###########",
+            "X.........#",
+            "###v#######",
+            "..........E",
+            "###########",
+        ],
+        true,
+    );
+    assert_reaches_exit(
+        &[
+            "###########",
+            ".........E#",
+            "###v#######",
+            "X..........",
+            "###########",
+        ],
+        false,
+    );
+
+    assert_reaches_exit(&["##########", "X.I#..O.E#", "##PPPPP###"], true);
+    assert_reaches_exit(&["##########", "X.I#..O.E#", "##PP.PP###"], false);
+}
+
+#[test]
+fn reach_power_ups() {
+    // Power-ups aren't used, but are reported when out of reach
+    let reach = assert_reaches_exit(
+        &["########", "X.B....#", "########", "..R...E#", "########"],
+        false,
+    );
+    assert_eq!(reach.unreachable, vec![16 * 32 + 2]);
+}
+
+#[test]
+fn difficulty_order() {
+    // A ladder of levels that introduce one mechanic each
+    let ladder: Vec<Vec<Block>> = [
+        &["X......E", "########"][..],
+        &["X..#..E", "#######"],
+        &["##########", "X.K..L..E#", "##########"],
+        &["##########", "...c..T..#", "X.....T.E#", "##########"],
+        &["########", "X.B....#", "########", "......E#", "########"],
+        &[
+            "###########",
+            "X.R....L.E#",
+            "###v#######",
+            "...K......#",
+            "###########",
+        ],
+    ]
+    .iter()
+    .map(|rows| level(rows))
+    .collect();
+
+    let options = DifficultyOptions::new();
+    let estimates: Vec<Difficulty> = ladder
+        .iter()
+        .map(|blocks| difficulty::estimate(blocks, &options).unwrap())
+        .collect();
+    let scores: Vec<f32> = estimates.iter().map(|d| d.score).collect();
+    assert!(scores.windows(2).all(|w| w[0] < w[1]), "{:?}", scores);
+
+    for estimate in estimates.iter() {
+        let total: f32 = estimate.breakdown.iter().map(|(_, points)| points).sum();
+        assert!((total - estimate.score).abs() < 1e-3);
+    }
+    assert_eq!(estimates[0].features.jumps, 0);
+    assert!(estimates[1].features.jumps > 0);
+    assert_eq!(estimates[2].features.locks, 1);
+    assert_eq!(estimates[3].features.switches, 1);
+    assert_eq!(estimates[4].features.burrows, 1);
+    assert_eq!(estimates[5].features.recalls, 1);
+
+    // The defaults already order the ladder, so calibrating keeps them
+    let features: Vec<_> = estimates.into_iter().map(|d| d.features).collect();
+    let (weights, inversions) = difficulty::calibrate(&features);
+    assert_eq!(inversions, 0);
+    assert_eq!(weights, DifficultyWeights::new());
+
+    // Reversing the order needs different weights
+    let reversed: Vec<_> = features.iter().rev().cloned().collect();
+    let (weights, _) = difficulty::calibrate(&reversed);
+    assert_ne!(weights, DifficultyWeights::new());
+
+    assert_eq!(
+        difficulty::estimate(
+            &level(&["...#...", "...#...", "X..#..E", "#######"]),
+            &options
+        ),
+        Err(DifficultyError::Unsolvable)
+    );
+}
@@ -0,0 +1,211 @@
+use image::GenericImageView;
+use sks::{
+    block::BackgroundType,
+    render::{
+        texture::{
+            AtlasDefinition,
+            Rect,
+            TextureError,
+        },
+        ImageRenderer,
+        RenderError,
+        RenderOptions,
+        TexturePack,
+    },
+    Block,
+};
+use std::io::{
+    Cursor,
+    Write,
+};
+
+fn atlas() -> image::DynamicImage {
+    // A red sprite on the left, a green one on the right
+    let mut atlas = image::RgbaImage::from_pixel(8, 4, image::Rgba([255, 0, 0, 255]));
+    for x in 4..8 {
+        for y in 0..4 {
+            atlas.put_pixel(x, y, image::Rgba([0, 255, 0, 255]));
+        }
+    }
+    image::DynamicImage::ImageRgba8(atlas)
+}
+
+fn solid_png(color: [u8; 4]) -> Vec<u8> {
+    let img =
+        image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(4, 4, image::Rgba(color)));
+    let mut data = Vec::new();
+    img.write_to(&mut data, image::ImageOutputFormat::Png)
+        .unwrap();
+    data
+}
+
+#[test]
+fn texture_pack_dir() {
+    let dir = std::env::temp_dir().join(format!("sks-texture-pack-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("red.png"), solid_png([255, 0, 0, 255])).unwrap();
+    std::fs::write(
+        dir.join("manifest.txt"),
+        "# A custom block\nB0 = red.png\n\n",
+    )
+    .unwrap();
+
+    let pack = TexturePack::from_dir(&dir);
+    // Files outside of the pack can't be used
+    std::fs::write(dir.join("manifest.txt"), "B0 = ../red.png\n").unwrap();
+    let outside = TexturePack::from_dir(&dir);
+    std::fs::write(dir.join("manifest.txt"), "B0 = /etc/passwd\n").unwrap();
+    let absolute = TexturePack::from_dir(&dir);
+    std::fs::remove_dir_all(&dir).unwrap();
+    let pack = pack.unwrap();
+    assert!(matches!(outside, Err(TextureError::InvalidPath(_))));
+    assert!(matches!(absolute, Err(TextureError::InvalidPath(_))));
+    assert!(pack.contains(&Block::Block));
+    assert!(!pack.contains(&Block::Key));
+
+    let mut blocks = vec![Block::Empty; sks::LEVEL_SIZE];
+    blocks[0] = Block::Block;
+
+    let renderer = ImageRenderer::with_textures(pack);
+    let img = renderer
+        .render(&blocks, &RenderOptions::new().integer_scale(4))
+        .unwrap();
+    assert_eq!(img.get_pixel(1, 1).0, [255, 0, 0, 255]);
+}
+
+#[test]
+fn texture_pack_zip() {
+    let mut blocks = vec![Block::Empty; sks::LEVEL_SIZE];
+    blocks[0] = Block::Background {
+        background_type: BackgroundType::Waterfall,
+    };
+
+    let renderer = ImageRenderer::new();
+    let opts = RenderOptions::new().integer_scale(4);
+    assert!(matches!(
+        renderer.render(&blocks, &opts),
+        Err(RenderError::MissingBackgroundTexture)
+    ));
+
+    // No manifest, so files are named after textures
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("M1.png", Default::default()).unwrap();
+    zip.write_all(&solid_png([0, 0, 255, 255])).unwrap();
+    let data = zip.finish().unwrap().into_inner();
+
+    let pack = TexturePack::from_zip(Cursor::new(data)).unwrap();
+    let renderer = ImageRenderer::with_textures(pack);
+    let img = renderer.render(&blocks, &opts).unwrap();
+    assert_eq!(img.get_pixel(64, 36).0, [0, 0, 255, 255]);
+}
+
+#[test]
+fn texture_pack_zip_declared_size() {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let stored =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    zip.start_file("M1.png", stored).unwrap();
+    zip.write_all(&solid_png([0, 0, 255, 255])).unwrap();
+    let mut data = zip.finish().unwrap().into_inner();
+
+    // Claim a huge uncompressed size in the local and central headers
+    let huge = 0xffff_fff0u32.to_le_bytes();
+    for (signature, offset) in [
+        ([0x50, 0x4b, 0x03, 0x04], 22),
+        ([0x50, 0x4b, 0x01, 0x02], 24),
+    ]
+    .iter()
+    {
+        let start = data.windows(4).position(|w| w == signature).unwrap();
+        data[start + offset..start + offset + 4].copy_from_slice(&huge);
+    }
+
+    let pack = TexturePack::from_zip(Cursor::new(data)).unwrap();
+    assert!(pack.contains(&Block::Background {
+        background_type: BackgroundType::Waterfall,
+    }));
+}
+
+#[test]
+fn texture_pack_invalid_manifest() {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("manifest.txt", Default::default()).unwrap();
+    zip.write_all(b"B0 = block.png\nXX = other.png\n").unwrap();
+    zip.start_file("block.png", Default::default()).unwrap();
+    zip.write_all(&solid_png([0, 0, 0, 255])).unwrap();
+    let data = zip.finish().unwrap().into_inner();
+
+    match TexturePack::from_zip(Cursor::new(data)) {
+        Err(TextureError::UnknownTextureName(name)) => assert_eq!(name, "XX"),
+        res => panic!("unexpected result: {:?}", res.map(|_| ())),
+    }
+}
+
+#[test]
+fn texture_pack_atlas() {
+    let def = AtlasDefinition::parse("B0 = 0, 0, 4, 4\nIK = 4, 0, 4, 4\n").unwrap();
+    assert_eq!(def.get(&Block::Key), Some(Rect::new(4, 0, 4, 4)));
+
+    let pack = TexturePack::from_atlas(atlas(), &def).unwrap();
+    assert!(pack.contains(&Block::Block));
+    assert!(pack.contains(&Block::Key));
+
+    let mut blocks = vec![Block::Empty; sks::LEVEL_SIZE];
+    blocks[0] = Block::Block;
+    blocks[1] = Block::Key;
+
+    let renderer = ImageRenderer::with_textures(pack);
+    let img = renderer
+        .render(&blocks, &RenderOptions::new().integer_scale(4))
+        .unwrap();
+    assert_eq!(img.get_pixel(1, 1).0, [255, 0, 0, 255]);
+    assert_eq!(img.get_pixel(5, 1).0, [0, 255, 0, 255]);
+
+    let mut def = AtlasDefinition::new();
+    def.insert(&Block::Lock, Rect::new(6, 0, 4, 4));
+    assert!(matches!(
+        TexturePack::from_atlas(atlas(), &def),
+        Err(TextureError::InvalidSprite(_))
+    ));
+
+    // A bad sprite leaves the pack unchanged, even if others in the atlas are fine
+    def.insert(&Block::Exit, Rect::new(0, 0, 4, 4));
+    def.insert(&Block::Player, Rect::new(4, 0, 4, 4));
+    let mut pack = TexturePack::new();
+    assert!(matches!(
+        pack.add_atlas(atlas(), &def),
+        Err(TextureError::InvalidSprite(name)) if name == "BK"
+    ));
+    assert!(!pack.contains(&Block::Exit));
+    assert!(!pack.contains(&Block::Player));
+}
+
+#[test]
+fn texture_pack_atlas_manifest() {
+    let mut atlas_png = Vec::new();
+    atlas()
+        .write_to(&mut atlas_png, image::ImageOutputFormat::Png)
+        .unwrap();
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("manifest.txt", Default::default()).unwrap();
+    zip.write_all(b"atlas = sheet.png\nOU = 4, 0, 4, 4\nX0 = player.png\n")
+        .unwrap();
+    zip.start_file("sheet.png", Default::default()).unwrap();
+    zip.write_all(&atlas_png).unwrap();
+    zip.start_file("player.png", Default::default()).unwrap();
+    zip.write_all(&solid_png([0, 0, 255, 255])).unwrap();
+    let data = zip.finish().unwrap().into_inner();
+
+    let pack = TexturePack::from_zip(Cursor::new(data)).unwrap();
+    let up = pack
+        .get(&Block::OneWayWall {
+            direction: sks::block::Direction::Up,
+        })
+        .unwrap();
+    assert_eq!(up.get_pixel(0, 0).0, [0, 255, 0, 255]);
+    assert_eq!(
+        pack.get(&Block::Player).unwrap().get_pixel(0, 0).0,
+        [0, 0, 255, 255]
+    );
+}